@@ -0,0 +1,1132 @@
+use crate::oscillators::TempoClock;
+use crate::rack::*;
+use crate::{build, props, tag};
+use std::f32::consts::{FRAC_PI_4, PI, SQRT_2, TAU};
+use std::sync::Arc;
+
+/// A delay line that holds `seconds` of audio at `sample_rate` plus room for
+/// cubic interpolation. Modules clamp their delays to its length, so at higher
+/// sample rates the longest delays are cut short.
+fn delay_buffer(seconds: f32, sample_rate: f32) -> RingBuffer {
+    RingBuffer::new(0, vec![0.0; (seconds * sample_rate) as usize + 4])
+}
+
+/// Advance the internal lfo phase in `state[(tag, 0)]` and return its value
+/// before the step.
+fn lfo_step(rack: &mut Rack, tag: Tag, hz: f32, sample_rate: f32) -> f32 {
+    let phase = rack.state[(tag, 0)];
+    rack.state[(tag, 0)] = (phase + hz / sample_rate).rem_euclid(1.0);
+    phase
+}
+
+/// A multi-voice stereo chorus. Each voice reads the input through a delay of
+/// `delay` plus up to `depth` seconds, swept by an internal sine lfo at `rate`
+/// Hz. The voices' lfos are evenly spaced in phase and panned across the
+/// stereo field by `spread`. Left and right are in output slots 0 and 1.
+#[derive(Debug, Copy, Clone)]
+pub struct Chorus {
+    tag: Tag,
+    wave: Tag,
+    voices: usize,
+}
+
+impl Chorus {
+    pub fn new<T: Into<Tag>>(tag: T, wave: Tag, voices: usize) -> Self {
+        Self {
+            tag: tag.into(),
+            wave,
+            voices,
+        }
+    }
+
+    props!(rate, set_rate, 0);
+    props!(depth, set_depth, 1);
+    props!(delay, set_delay, 2);
+    props!(mix, set_mix, 3);
+    props!(spread, set_spread, 4);
+
+    /// Position of voice `v` in [-1, 1].
+    fn position(&self, v: usize) -> f32 {
+        if self.voices == 1 {
+            0.0
+        } else {
+            2.0 * v as f32 / (self.voices - 1) as f32 - 1.0
+        }
+    }
+}
+
+impl Signal for Chorus {
+    tag!();
+    fn signal(&self, rack: &mut Rack, sample_rate: f32) {
+        let input = rack.outputs[(self.wave, 0)];
+        let depth = self.depth(rack);
+        let delay = self.delay(rack);
+        let mix = self.mix(rack).clamp(0.0, 1.0);
+        let spread = self.spread(rack).clamp(0.0, 1.0);
+        let phase = lfo_step(rack, self.tag, self.rate(rack), sample_rate);
+
+        let buffer = rack.buffers.buffers_mut(self.tag);
+        buffer.push(input);
+        let max = buffer.len() as f32 - 3.0;
+        let gain = SQRT_2 / (self.voices as f32).sqrt();
+        let (mut left, mut right) = (0.0, 0.0);
+        for v in 0..self.voices {
+            let lfo = (TAU * (phase + v as f32 / self.voices as f32)).sin();
+            let d = ((delay + depth * (1.0 + lfo) / 2.0) * sample_rate).clamp(1.0, max);
+            let out = gain * buffer.get_cubic(d);
+            let pan = (spread * self.position(v) + 1.0) * FRAC_PI_4;
+            left += pan.cos() * out;
+            right += pan.sin() * out;
+        }
+        rack.outputs[(self.tag, 0)] = (1.0 - mix) * input + mix * left;
+        rack.outputs[(self.tag, 1)] = (1.0 - mix) * input + mix * right;
+    }
+}
+
+#[derive(Debug, Copy, Clone)]
+pub struct ChorusBuilder {
+    wave: Tag,
+    voices: usize,
+    max_delay: f32,
+    max_sample_rate: f32,
+    rate: Control,
+    depth: Control,
+    delay: Control,
+    mix: Control,
+    spread: Control,
+}
+
+impl ChorusBuilder {
+    pub fn new(wave: Tag) -> Self {
+        Self {
+            wave,
+            voices: 3,
+            max_delay: 0.05,
+            max_sample_rate: MAX_SAMPLE_RATE,
+            rate: 0.8.into(),
+            depth: 0.003.into(),
+            delay: 0.012.into(),
+            mix: 0.5.into(),
+            spread: 1.0.into(),
+        }
+    }
+
+    build!(rate);
+    build!(depth);
+    build!(delay);
+    build!(mix);
+    build!(spread);
+
+    pub fn voices(&mut self, value: usize) -> &mut Self {
+        assert!(value > 0, "a chorus needs at least one voice");
+        self.voices = value;
+        self
+    }
+
+    /// The longest `delay` plus `depth` in seconds, defaults to 0.05.
+    pub fn max_delay(&mut self, value: f32) -> &mut Self {
+        self.max_delay = value;
+        self
+    }
+
+    /// The highest sample rate the delay line is sized for, defaults to
+    /// [`MAX_SAMPLE_RATE`].
+    pub fn max_sample_rate(&mut self, value: f32) -> &mut Self {
+        self.max_sample_rate = value;
+        self
+    }
+
+    pub fn rack(&self, rack: &mut Rack) -> Arc<Chorus> {
+        let n = rack.num_modules();
+        rack.controls[(n, 0)] = self.rate;
+        rack.controls[(n, 1)] = self.depth;
+        rack.controls[(n, 2)] = self.delay;
+        rack.controls[(n, 3)] = self.mix;
+        rack.controls[(n, 4)] = self.spread;
+        let chorus = Arc::new(Chorus::new(n, self.wave, self.voices));
+        rack.buffers.set_buffer(
+            chorus.tag,
+            delay_buffer(self.max_delay, self.max_sample_rate),
+        );
+        rack.push(chorus.clone());
+        chorus
+    }
+}
+
+/// A flanger with feedback. The input is delayed by `delay` plus up to `depth`
+/// seconds, swept by an internal sine lfo at `rate` Hz, and mixed back with
+/// the dry signal. `invert` flips the polarity of the delayed signal and the
+/// feedback, moving the comb's notches to where the peaks were.
+#[derive(Debug, Copy, Clone)]
+pub struct Flanger {
+    tag: Tag,
+    wave: Tag,
+}
+
+impl Flanger {
+    pub fn new<T: Into<Tag>>(tag: T, wave: Tag) -> Self {
+        Self {
+            tag: tag.into(),
+            wave,
+        }
+    }
+
+    props!(rate, set_rate, 0);
+    props!(depth, set_depth, 1);
+    props!(delay, set_delay, 2);
+    props!(mix, set_mix, 3);
+    props!(feedback, set_feedback, 4);
+
+    pub fn invert(&self, rack: &Rack) -> bool {
+        let inp = rack.controls[(self.tag, 5)];
+        rack.outputs.boolean(inp).unwrap()
+    }
+
+    pub fn set_invert(&self, rack: &mut Rack, value: Control) {
+        rack.controls[(self.tag, 5)] = value;
+    }
+}
+
+impl Signal for Flanger {
+    tag!();
+    fn signal(&self, rack: &mut Rack, sample_rate: f32) {
+        let input = rack.outputs[(self.wave, 0)];
+        let depth = self.depth(rack);
+        let delay = self.delay(rack);
+        let mix = self.mix(rack).clamp(0.0, 1.0);
+        let feedback = self.feedback(rack).clamp(-0.99, 0.99);
+        let polarity = if self.invert(rack) { -1.0 } else { 1.0 };
+        let phase = lfo_step(rack, self.tag, self.rate(rack), sample_rate);
+
+        let lfo = (TAU * phase).sin();
+        let buffer = rack.buffers.buffers_mut(self.tag);
+        let max = buffer.len() as f32 - 3.0;
+        let d = ((delay + depth * (1.0 + lfo) / 2.0) * sample_rate).clamp(2.0, max);
+        // Read before writing so the feedback path has at least one sample of delay.
+        let delayed = polarity * buffer.get_cubic(d - 1.0);
+        buffer.push(input + feedback * delayed);
+        rack.outputs[(self.tag, 0)] = (1.0 - mix) * input + mix * delayed;
+    }
+}
+
+#[derive(Debug, Copy, Clone)]
+pub struct FlangerBuilder {
+    wave: Tag,
+    max_delay: f32,
+    max_sample_rate: f32,
+    rate: Control,
+    depth: Control,
+    delay: Control,
+    mix: Control,
+    feedback: Control,
+    invert: Control,
+}
+
+impl FlangerBuilder {
+    pub fn new(wave: Tag) -> Self {
+        Self {
+            wave,
+            max_delay: 0.02,
+            max_sample_rate: MAX_SAMPLE_RATE,
+            rate: 0.25.into(),
+            depth: 0.002.into(),
+            delay: 0.001.into(),
+            mix: 0.5.into(),
+            feedback: 0.5.into(),
+            invert: false.into(),
+        }
+    }
+
+    build!(rate);
+    build!(depth);
+    build!(delay);
+    build!(mix);
+    build!(feedback);
+    build!(invert);
+
+    /// The longest `delay` plus `depth` in seconds, defaults to 0.02.
+    pub fn max_delay(&mut self, value: f32) -> &mut Self {
+        self.max_delay = value;
+        self
+    }
+
+    /// The highest sample rate the delay line is sized for, defaults to
+    /// [`MAX_SAMPLE_RATE`].
+    pub fn max_sample_rate(&mut self, value: f32) -> &mut Self {
+        self.max_sample_rate = value;
+        self
+    }
+
+    pub fn rack(&self, rack: &mut Rack) -> Arc<Flanger> {
+        let n = rack.num_modules();
+        rack.controls[(n, 0)] = self.rate;
+        rack.controls[(n, 1)] = self.depth;
+        rack.controls[(n, 2)] = self.delay;
+        rack.controls[(n, 3)] = self.mix;
+        rack.controls[(n, 4)] = self.feedback;
+        rack.controls[(n, 5)] = self.invert;
+        let flanger = Arc::new(Flanger::new(n, self.wave));
+        rack.buffers.set_buffer(
+            flanger.tag,
+            delay_buffer(self.max_delay, self.max_sample_rate),
+        );
+        rack.push(flanger.clone());
+        flanger
+    }
+}
+
+// Phaser state layout: the lfo phase in 0, each channel's last output for the
+// feedback path in 1 and 2, then the previous input and output of every stage,
+// left channel first.
+const PHASER_FEEDBACK: usize = 1;
+const PHASER_STATE_OFFSET: usize = 3;
+const PHASER_MAX_STAGES: usize = 12;
+
+/// A phaser: a chain of 4 to 12 first-order allpass stages whose break
+/// frequency sweeps `depth` octaves either side of `centre` Hz, mixed with the
+/// dry signal to notch the spectrum. The sweep comes from an internal sine lfo
+/// at `rate` Hz, or from an external modulator in [-1, 1] set with
+/// [`PhaserBuilder::modulation`]. The right channel's lfo is `stereo` cycles
+/// ahead of the left. Left and right are in output slots 0 and 1.
+#[derive(Debug, Copy, Clone)]
+pub struct Phaser {
+    tag: Tag,
+    wave: Tag,
+    stages: usize,
+}
+
+impl Phaser {
+    pub fn new<T: Into<Tag>>(tag: T, wave: Tag, stages: usize) -> Self {
+        assert!(
+            (4..=PHASER_MAX_STAGES).contains(&stages),
+            "a phaser needs 4 to 12 stages, not {stages}"
+        );
+        Self {
+            tag: tag.into(),
+            wave,
+            stages,
+        }
+    }
+
+    props!(rate, set_rate, 0);
+    props!(depth, set_depth, 1);
+    props!(centre, set_centre, 2);
+    props!(feedback, set_feedback, 3);
+    props!(mix, set_mix, 4);
+    props!(stereo, set_stereo, 5);
+
+    pub fn stages(&self) -> usize {
+        self.stages
+    }
+
+    /// True when the sweep follows an external modulator.
+    pub fn modulated(&self, rack: &Rack) -> bool {
+        matches!(rack.controls[(self.tag, 6)], Control::V(..))
+    }
+
+    pub fn set_modulation(&self, rack: &mut Rack, modulator: Option<Tag>) {
+        rack.controls[(self.tag, 6)] = match modulator {
+            Some(m) => Control::V(m, 0),
+            None => false.into(),
+        };
+    }
+
+    /// Run one sample through the allpass chain of `channel` with break
+    /// frequency `hz`.
+    fn chain(&self, rack: &mut Rack, channel: usize, x: f32, hz: f32, sample_rate: f32) -> f32 {
+        let t = (PI * hz / sample_rate).tan();
+        let a = (t - 1.0) / (t + 1.0);
+        let first = PHASER_STATE_OFFSET + 2 * channel * PHASER_MAX_STAGES;
+        let state = rack.state.state_mut(self.tag);
+        let mut x = x;
+        for s in 0..self.stages {
+            let i = first + 2 * s;
+            let y = a * x + state[i] - a * state[i + 1];
+            state[i] = x;
+            state[i + 1] = y;
+            x = y;
+        }
+        x
+    }
+}
+
+impl Signal for Phaser {
+    tag!();
+    fn signal(&self, rack: &mut Rack, sample_rate: f32) {
+        let input = rack.outputs[(self.wave, 0)];
+        let depth = self.depth(rack);
+        let centre = self.centre(rack);
+        let feedback = self.feedback(rack).clamp(-0.95, 0.95);
+        let mix = self.mix(rack).clamp(0.0, 1.0);
+        let stereo = self.stereo(rack);
+        let modulation = if self.modulated(rack) {
+            let m = rack.outputs.value(rack.controls[(self.tag, 6)]).unwrap();
+            [m, m]
+        } else {
+            let phase = lfo_step(rack, self.tag, self.rate(rack), sample_rate);
+            [(TAU * phase).sin(), (TAU * (phase + stereo)).sin()]
+        };
+        let nyquist = 0.49 * sample_rate;
+        for (channel, m) in modulation.iter().enumerate() {
+            let hz = (centre * 2f32.powf(depth * m.clamp(-1.0, 1.0))).clamp(1.0, nyquist);
+            let x = input + feedback * rack.state[(self.tag, PHASER_FEEDBACK + channel)];
+            let wet = self.chain(rack, channel, x, hz, sample_rate);
+            rack.state[(self.tag, PHASER_FEEDBACK + channel)] = wet;
+            rack.outputs[(self.tag, channel)] = (1.0 - mix) * input + mix * wet;
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone)]
+pub struct PhaserBuilder {
+    wave: Tag,
+    stages: usize,
+    rate: Control,
+    depth: Control,
+    centre: Control,
+    feedback: Control,
+    mix: Control,
+    stereo: Control,
+    modulation: Control,
+}
+
+impl PhaserBuilder {
+    pub fn new(wave: Tag) -> Self {
+        Self {
+            wave,
+            stages: 6,
+            rate: 0.3.into(),
+            depth: 2.0.into(),
+            centre: 800.0.into(),
+            feedback: 0.5.into(),
+            mix: 0.5.into(),
+            stereo: 0.25.into(),
+            modulation: false.into(),
+        }
+    }
+
+    build!(rate);
+    build!(depth);
+    build!(centre);
+    build!(feedback);
+    build!(mix);
+    build!(stereo);
+
+    /// The number of allpass stages, 4 to 12.
+    pub fn stages(&mut self, value: usize) -> &mut Self {
+        self.stages = value;
+        self
+    }
+
+    /// Sweep with output slot 0 of `modulator` instead of the internal lfo.
+    pub fn modulation(&mut self, modulator: Tag) -> &mut Self {
+        self.modulation = Control::V(modulator, 0);
+        self
+    }
+
+    pub fn rack(&self, rack: &mut Rack) -> Arc<Phaser> {
+        let n = rack.num_modules();
+        rack.controls[(n, 0)] = self.rate;
+        rack.controls[(n, 1)] = self.depth;
+        rack.controls[(n, 2)] = self.centre;
+        rack.controls[(n, 3)] = self.feedback;
+        rack.controls[(n, 4)] = self.mix;
+        rack.controls[(n, 5)] = self.stereo;
+        rack.controls[(n, 6)] = self.modulation;
+        let phaser = Arc::new(Phaser::new(n, self.wave, self.stages));
+        rack.push(phaser.clone());
+        phaser
+    }
+}
+
+// Length of the crossfade between the old and new taps when the delay time of
+// an `EchoDelay` changes.
+const ECHO_FADE: f32 = 0.05;
+
+// EchoDelay state layout: the feedback lowpass of each channel in 0 and 1, the
+// lowpass used to derive the highpass in 2 and 3, the current and previous
+// delay times in 4 and 5, the remaining crossfade in 6 and 1.0 in 7 once the
+// first sample has set the delay time.
+const ECHO_TIME: usize = 4;
+const ECHO_FADING: usize = 6;
+const ECHO_STARTED: usize = 7;
+
+/// A stereo echo with feedback, clamped below 1. The repeats pass through a one pole `lowpass`
+/// and `highpass` (cutoffs in Hz) so they darken and thin out as they decay.
+/// The delay is `time` seconds, or `division` beats at the tempo a
+/// [`TempoClock`] publishes in its `BPM` slot when built with
+/// [`EchoDelayBuilder::sync`]. When the time changes the old and new
+/// taps are crossfaded over 50 ms so there are no clicks. In `ping_pong` mode
+/// the input enters the left line and each repeat crosses to the other side.
+/// Left and right are in output slots 0 and 1.
+#[derive(Debug, Copy, Clone)]
+pub struct EchoDelay {
+    tag: Tag,
+    wave: Tag,
+    max_time: f32,
+}
+
+impl EchoDelay {
+    pub fn new<T: Into<Tag>>(tag: T, wave: Tag, max_time: f32) -> Self {
+        Self {
+            tag: tag.into(),
+            wave,
+            max_time,
+        }
+    }
+
+    props!(time, set_time, 0);
+    props!(feedback, set_feedback, 1);
+    props!(lowpass, set_lowpass, 2);
+    props!(highpass, set_highpass, 3);
+    props!(mix, set_mix, 4);
+    props!(division, set_division, 7);
+
+    pub fn ping_pong(&self, rack: &Rack) -> bool {
+        let inp = rack.controls[(self.tag, 5)];
+        rack.outputs.boolean(inp).unwrap()
+    }
+
+    pub fn set_ping_pong(&self, rack: &mut Rack, value: Control) {
+        rack.controls[(self.tag, 5)] = value;
+    }
+
+    pub fn synced(&self, rack: &Rack) -> bool {
+        matches!(rack.controls[(self.tag, 6)], Control::V(..))
+    }
+
+    pub fn set_sync(&self, rack: &mut Rack, clock: Option<Tag>) {
+        rack.controls[(self.tag, 6)] = match clock {
+            Some(c) => Control::V(c, TempoClock::BPM),
+            None => false.into(),
+        };
+    }
+
+    /// The delay time in seconds, following the clock's tempo when synced.
+    pub fn delay_time(&self, rack: &Rack) -> f32 {
+        match rack.controls[(self.tag, 6)] {
+            Control::V(..) => {
+                let bpm = rack.outputs.value(rack.controls[(self.tag, 6)]).unwrap();
+                self.division(rack) * 60.0 / bpm.max(1.0)
+            }
+            _ => self.time(rack),
+        }
+    }
+
+    /// Frames of left and right samples needed for `max_time` seconds.
+    fn frames(max_time: f32, sample_rate: f32) -> usize {
+        (max_time * sample_rate) as usize + 4
+    }
+
+    fn tap(buffer: &RingBuffer, channel: usize, time: f32, sample_rate: f32) -> f32 {
+        let frames = buffer.len() / 2;
+        // Read before writing, the last pushed frame is 1 sample old.
+        let age = (time * sample_rate - 1.0).clamp(1.0, frames as f32 - 3.0);
+        buffer.get_cubic_frame(age, channel, 2)
+    }
+}
+
+impl Signal for EchoDelay {
+    tag!();
+    fn signal(&self, rack: &mut Rack, sample_rate: f32) {
+        let tag = self.tag;
+        let input = rack.outputs[(self.wave, 0)];
+        let feedback = self.feedback(rack).clamp(0.0, 0.99);
+        let mix = self.mix(rack).clamp(0.0, 1.0);
+        let ping_pong = self.ping_pong(rack);
+        let nyquist = 0.49 * sample_rate;
+        let lp = (-TAU * self.lowpass(rack).clamp(1.0, nyquist) / sample_rate).exp();
+        let hp = (-TAU * self.highpass(rack).clamp(1.0, nyquist) / sample_rate).exp();
+
+        let target = self.delay_time(rack).clamp(0.0, self.max_time);
+        let state = rack.state.state_mut(tag);
+        if state[ECHO_STARTED] == 0.0 {
+            state[ECHO_TIME] = target;
+            state[ECHO_STARTED] = 1.0;
+        } else if state[ECHO_FADING] <= 0.0 && target != state[ECHO_TIME] {
+            state[ECHO_TIME + 1] = state[ECHO_TIME];
+            state[ECHO_TIME] = target;
+            state[ECHO_FADING] = 1.0;
+        }
+        let (time, old_time, fade) = (state[ECHO_TIME], state[ECHO_TIME + 1], state[ECHO_FADING]);
+        state[ECHO_FADING] = (fade - 1.0 / (ECHO_FADE * sample_rate)).max(0.0);
+
+        let buffer = rack.buffers.buffers(tag);
+        let mut wet = [0.0; 2];
+        for (channel, w) in wet.iter_mut().enumerate() {
+            let new = Self::tap(buffer, channel, time, sample_rate);
+            *w = if fade > 0.0 {
+                let old = Self::tap(buffer, channel, old_time, sample_rate);
+                fade * old + (1.0 - fade) * new
+            } else {
+                new
+            };
+        }
+
+        let state = rack.state.state_mut(tag);
+        let mut repeats = [0.0; 2];
+        for (channel, r) in repeats.iter_mut().enumerate() {
+            state[channel] = (1.0 - lp) * wet[channel] + lp * state[channel];
+            state[2 + channel] = (1.0 - hp) * state[channel] + hp * state[2 + channel];
+            *r = feedback * (state[channel] - state[2 + channel]);
+        }
+        let buffer = rack.buffers.buffers_mut(tag);
+        if ping_pong {
+            buffer.push(input + repeats[1]);
+            buffer.push(repeats[0]);
+        } else {
+            buffer.push(input + repeats[0]);
+            buffer.push(input + repeats[1]);
+        }
+
+        rack.outputs[(tag, 0)] = (1.0 - mix) * input + mix * wet[0];
+        rack.outputs[(tag, 1)] = (1.0 - mix) * input + mix * wet[1];
+    }
+}
+
+#[derive(Debug, Copy, Clone)]
+pub struct EchoDelayBuilder {
+    wave: Tag,
+    max_time: f32,
+    max_sample_rate: f32,
+    time: Control,
+    feedback: Control,
+    lowpass: Control,
+    highpass: Control,
+    mix: Control,
+    ping_pong: Control,
+    sync: Control,
+    division: Control,
+}
+
+impl EchoDelayBuilder {
+    pub fn new(wave: Tag) -> Self {
+        Self {
+            wave,
+            max_time: 2.0,
+            max_sample_rate: MAX_SAMPLE_RATE,
+            time: 0.375.into(),
+            feedback: 0.4.into(),
+            lowpass: 8_000.0.into(),
+            highpass: 40.0.into(),
+            mix: 0.5.into(),
+            ping_pong: false.into(),
+            sync: false.into(),
+            division: 0.75.into(),
+        }
+    }
+
+    build!(time);
+    build!(feedback);
+    build!(lowpass);
+    build!(highpass);
+    build!(mix);
+    build!(ping_pong);
+    build!(division);
+
+    /// Take the delay time from `division` beats of `clock`.
+    pub fn sync(&mut self, clock: Tag) -> &mut Self {
+        self.sync = Control::V(clock, TempoClock::BPM);
+        self
+    }
+
+    /// The longest delay time in seconds, defaults to 2.
+    pub fn max_time(&mut self, value: f32) -> &mut Self {
+        self.max_time = value;
+        self
+    }
+
+    /// The highest sample rate the delay lines are sized for, defaults to
+    /// [`MAX_SAMPLE_RATE`].
+    pub fn max_sample_rate(&mut self, value: f32) -> &mut Self {
+        self.max_sample_rate = value;
+        self
+    }
+
+    pub fn rack(&self, rack: &mut Rack) -> Arc<EchoDelay> {
+        let n = rack.num_modules();
+        rack.controls[(n, 0)] = self.time;
+        rack.controls[(n, 1)] = self.feedback;
+        rack.controls[(n, 2)] = self.lowpass;
+        rack.controls[(n, 3)] = self.highpass;
+        rack.controls[(n, 4)] = self.mix;
+        rack.controls[(n, 5)] = self.ping_pong;
+        rack.controls[(n, 6)] = self.sync;
+        rack.controls[(n, 7)] = self.division;
+        let echo = Arc::new(EchoDelay::new(n, self.wave, self.max_time));
+        let len = 2 * EchoDelay::frames(self.max_time, self.max_sample_rate);
+        rack.buffers
+            .set_buffer(echo.tag, RingBuffer::new(0, vec![0.0; len]));
+        rack.push(echo.clone());
+        echo
+    }
+}
+
+/// A feed-forward compressor. The peak level of the input, or of an optional
+/// sidechain, is compared with `threshold` (dB) and anything above it is
+/// reduced by `ratio`, with a soft `knee` (dB) either side of the threshold.
+/// The gain reduction rises in `attack` and falls in `release` seconds. A
+/// `lookahead` (seconds) delays the audio, not the detector, so the gain is
+/// already down when a transient arrives. `makeup` is in dB.
+///
+/// The compressed signal is in output slot 0 and the current gain reduction
+/// in dB, zero or positive, in slot 1 for metering.
+#[derive(Debug, Copy, Clone)]
+pub struct Compressor {
+    tag: Tag,
+    wave: Tag,
+}
+
+impl Compressor {
+    pub fn new<T: Into<Tag>>(tag: T, wave: Tag) -> Self {
+        Self {
+            tag: tag.into(),
+            wave,
+        }
+    }
+
+    props!(threshold, set_threshold, 0);
+    props!(ratio, set_ratio, 1);
+    props!(knee, set_knee, 2);
+    props!(attack, set_attack, 3);
+    props!(release, set_release, 4);
+    props!(makeup, set_makeup, 5);
+    props!(lookahead, set_lookahead, 6);
+
+    pub fn sidechained(&self, rack: &Rack) -> bool {
+        matches!(rack.controls[(self.tag, 7)], Control::V(..))
+    }
+
+    pub fn set_sidechain(&self, rack: &mut Rack, sidechain: Option<Tag>) {
+        rack.controls[(self.tag, 7)] = match sidechain {
+            Some(s) => Control::V(s, 0),
+            None => false.into(),
+        };
+    }
+
+    /// The static curve, the gain change in dB for an input level in dB.
+    pub fn gain_db(&self, rack: &Rack, level: f32) -> f32 {
+        let threshold = self.threshold(rack);
+        let slope = 1.0 / self.ratio(rack).max(1.0) - 1.0;
+        let knee = self.knee(rack).max(0.0);
+        let over = level - threshold;
+        if 2.0 * over <= -knee {
+            0.0
+        } else if 2.0 * over.abs() < knee {
+            slope * (over + knee / 2.0).powi(2) / (2.0 * knee)
+        } else {
+            slope * over
+        }
+    }
+}
+
+impl Signal for Compressor {
+    tag!();
+    fn signal(&self, rack: &mut Rack, sample_rate: f32) {
+        let tag = self.tag;
+        let input = rack.outputs[(self.wave, 0)];
+        let detector = match rack.controls[(tag, 7)] {
+            Control::V(..) => rack.outputs.value(rack.controls[(tag, 7)]).unwrap(),
+            _ => input,
+        };
+        let level = 20.0 * detector.abs().max(1e-6).log10();
+        let target = self.gain_db(rack, level);
+        // Smooth the gain in dB, falling (more reduction) at the attack rate.
+        let previous = rack.state[(tag, 0)];
+        let time = if target < previous {
+            self.attack(rack)
+        } else {
+            self.release(rack)
+        };
+        let coef = (-1.0 / (time.max(1e-5) * sample_rate)).exp();
+        let gain = target + coef * (previous - target);
+        rack.state[(tag, 0)] = gain;
+
+        let buffer = rack.buffers.buffers_mut(tag);
+        buffer.push(input);
+        let max = buffer.len() as f32 - 1.0;
+        let lookahead = (self.lookahead(rack) * sample_rate).round().clamp(0.0, max);
+        let delayed = rack.buffers.buffers(tag).get(lookahead);
+        let makeup = self.makeup(rack);
+        rack.outputs[(tag, 0)] = delayed * 10f32.powf((gain + makeup) / 20.0);
+        rack.outputs[(tag, 1)] = -gain;
+    }
+}
+
+#[derive(Debug, Copy, Clone)]
+pub struct CompressorBuilder {
+    wave: Tag,
+    max_lookahead: f32,
+    max_sample_rate: f32,
+    threshold: Control,
+    ratio: Control,
+    knee: Control,
+    attack: Control,
+    release: Control,
+    makeup: Control,
+    lookahead: Control,
+    sidechain: Control,
+}
+
+impl CompressorBuilder {
+    pub fn new(wave: Tag) -> Self {
+        Self {
+            wave,
+            max_lookahead: 0.02,
+            max_sample_rate: MAX_SAMPLE_RATE,
+            threshold: (-20.0).into(),
+            ratio: 4.0.into(),
+            knee: 6.0.into(),
+            attack: 0.01.into(),
+            release: 0.1.into(),
+            makeup: 0.0.into(),
+            lookahead: 0.0.into(),
+            sidechain: false.into(),
+        }
+    }
+
+    build!(threshold);
+    build!(ratio);
+    build!(knee);
+    build!(attack);
+    build!(release);
+    build!(makeup);
+    build!(lookahead);
+
+    /// Detect the level of output slot 0 of `sidechain` instead of the input.
+    pub fn sidechain(&mut self, sidechain: Tag) -> &mut Self {
+        self.sidechain = Control::V(sidechain, 0);
+        self
+    }
+
+    /// The longest lookahead in seconds, defaults to 0.02.
+    pub fn max_lookahead(&mut self, value: f32) -> &mut Self {
+        self.max_lookahead = value;
+        self
+    }
+
+    /// The highest sample rate the lookahead buffer is sized for, defaults
+    /// to [`MAX_SAMPLE_RATE`].
+    pub fn max_sample_rate(&mut self, value: f32) -> &mut Self {
+        self.max_sample_rate = value;
+        self
+    }
+
+    pub fn rack(&self, rack: &mut Rack) -> Arc<Compressor> {
+        let n = rack.num_modules();
+        rack.controls[(n, 0)] = self.threshold;
+        rack.controls[(n, 1)] = self.ratio;
+        rack.controls[(n, 2)] = self.knee;
+        rack.controls[(n, 3)] = self.attack;
+        rack.controls[(n, 4)] = self.release;
+        rack.controls[(n, 5)] = self.makeup;
+        rack.controls[(n, 6)] = self.lookahead;
+        rack.controls[(n, 7)] = self.sidechain;
+        let comp = Arc::new(Compressor::new(n, self.wave));
+        rack.buffers.set_buffer(
+            comp.tag,
+            delay_buffer(self.max_lookahead, self.max_sample_rate),
+        );
+        rack.push(comp.clone());
+        comp
+    }
+}
+
+/// Allpass coefficients of the two paths of a Hilbert transformer whose
+/// outputs are 90 degrees apart from about 20 Hz to 20 kHz at 44.1 kHz
+/// (Olli Niemitalo's design). Each section is a first-order allpass in z^-2.
+const HILBERT_I: [f32; 4] = [0.692_387_8, 0.936_065_4, 0.988_229_5, 0.998_748_8];
+const HILBERT_Q: [f32; 4] = [0.402_192_1, 0.856_171_1, 0.972_291, 0.995_288_5];
+
+// FrequencyShifter state layout: the oscillator phase in 0, the one sample
+// delay of the in-phase path in 1, then x[n-1], x[n-2], y[n-1], y[n-2] for
+// each allpass section, in-phase path first.
+const SHIFTER_STATE_OFFSET: usize = 2;
+
+fn hilbert_path(state: &mut [f32], coefficients: &[f32; 4], x: f32) -> f32 {
+    let mut x = x;
+    for (a, s) in coefficients.iter().zip(state.chunks_mut(4)) {
+        let y = a * a * (x + s[3]) - s[1];
+        s[1] = s[0];
+        s[0] = x;
+        s[3] = s[2];
+        s[2] = y;
+        x = y;
+    }
+    x
+}
+
+/// Shift every partial of the input by `hz`, unlike pitch shifting which
+/// scales them, for inharmonic and detuned effects. The input is split into a
+/// pair of signals 90 degrees apart by an allpass Hilbert transformer and
+/// single sideband modulated. The upshifted signal is in output slot `UP`
+/// and the downshifted one in `DOWN`.
+#[derive(Debug, Copy, Clone)]
+pub struct FrequencyShifter {
+    tag: Tag,
+    wave: Tag,
+}
+
+impl FrequencyShifter {
+    pub const UP: usize = 0;
+    pub const DOWN: usize = 1;
+
+    pub fn new<T: Into<Tag>>(tag: T, wave: Tag) -> Self {
+        Self {
+            tag: tag.into(),
+            wave,
+        }
+    }
+
+    props!(hz, set_hz, 0);
+}
+
+impl Signal for FrequencyShifter {
+    tag!();
+    fn signal(&self, rack: &mut Rack, sample_rate: f32) {
+        let input = rack.outputs[(self.wave, 0)];
+        let phase = lfo_step(rack, self.tag, self.hz(rack), sample_rate);
+        let state = rack.state.state_mut(self.tag);
+        let first = SHIFTER_STATE_OFFSET;
+        let i = hilbert_path(&mut state[first..first + 16], &HILBERT_I, input);
+        let q = hilbert_path(&mut state[first + 16..first + 32], &HILBERT_Q, input);
+        // The in-phase path runs one sample behind the quadrature one.
+        let i_delayed = state[1];
+        state[1] = i;
+        let (sin, cos) = (TAU * phase).sin_cos();
+        rack.outputs[(self.tag, Self::UP)] = i_delayed * cos + q * sin;
+        rack.outputs[(self.tag, Self::DOWN)] = i_delayed * cos - q * sin;
+    }
+}
+
+#[derive(Debug, Copy, Clone)]
+pub struct FrequencyShifterBuilder {
+    wave: Tag,
+    hz: Control,
+}
+
+impl FrequencyShifterBuilder {
+    pub fn new(wave: Tag) -> Self {
+        Self {
+            wave,
+            hz: 100.0.into(),
+        }
+    }
+
+    build!(hz);
+
+    pub fn rack(&self, rack: &mut Rack) -> Arc<FrequencyShifter> {
+        let n = rack.num_modules();
+        rack.controls[(n, 0)] = self.hz;
+        let fs = Arc::new(FrequencyShifter::new(n, self.wave));
+        rack.push(fs.clone());
+        fs
+    }
+}
+
+/// The voltage-current curve of one diode in Julian Parker's ring modulator
+/// model, zero below 0.2, quadratic up to 0.4 and linear beyond.
+fn diode(v: f32) -> f32 {
+    const VB: f32 = 0.2;
+    const VL: f32 = 0.4;
+    if v <= VB {
+        0.0
+    } else if v <= VL {
+        (v - VB).powi(2) / (2.0 * VL - 2.0 * VB)
+    } else {
+        v - VL + (VL - VB).powi(2) / (2.0 * VL - 2.0 * VB)
+    }
+}
+
+/// Ring modulation of a carrier by a modulator, giving the sum and difference
+/// frequencies for bell and metallic sounds. `mix` fades from the carrier
+/// alone at 0 to the ring modulated signal at 1. With `diode` on the product
+/// is replaced by a model of an analog diode ring, which adds odd order
+/// products such as three times the carrier plus and minus the modulator, and
+/// a small dead band around zero.
+#[derive(Debug, Copy, Clone)]
+pub struct RingMod {
+    tag: Tag,
+    carrier: Tag,
+    modulator: Tag,
+}
+
+impl RingMod {
+    pub fn new<T: Into<Tag>>(tag: T, carrier: Tag, modulator: Tag) -> Self {
+        Self {
+            tag: tag.into(),
+            carrier,
+            modulator,
+        }
+    }
+
+    props!(mix, set_mix, 0);
+
+    pub fn diode(&self, rack: &Rack) -> bool {
+        let inp = rack.controls[(self.tag, 1)];
+        rack.outputs.boolean(inp).unwrap()
+    }
+
+    pub fn set_diode(&self, rack: &mut Rack, value: Control) {
+        rack.controls[(self.tag, 1)] = value;
+    }
+}
+
+impl Signal for RingMod {
+    tag!();
+    fn signal(&self, rack: &mut Rack, _sample_rate: f32) {
+        let c = rack.outputs[(self.carrier, 0)];
+        let m = rack.outputs[(self.modulator, 0)];
+        let mix = self.mix(rack).clamp(0.0, 1.0);
+        let ring = if self.diode(rack) {
+            let pair = |v: f32| diode(v) + diode(-v);
+            pair(c + m / 2.0) - pair(c - m / 2.0)
+        } else {
+            c * m
+        };
+        rack.outputs[(self.tag, 0)] = (1.0 - mix) * c + mix * ring;
+    }
+}
+
+#[derive(Debug, Copy, Clone)]
+pub struct RingModBuilder {
+    carrier: Tag,
+    modulator: Tag,
+    mix: Control,
+    diode: Control,
+}
+
+impl RingModBuilder {
+    pub fn new(carrier: Tag, modulator: Tag) -> Self {
+        Self {
+            carrier,
+            modulator,
+            mix: 1.0.into(),
+            diode: false.into(),
+        }
+    }
+
+    build!(mix);
+    build!(diode);
+
+    pub fn rack(&self, rack: &mut Rack) -> Arc<RingMod> {
+        let n = rack.num_modules();
+        rack.controls[(n, 0)] = self.mix;
+        rack.controls[(n, 1)] = self.diode;
+        let rm = Arc::new(RingMod::new(n, self.carrier, self.modulator));
+        rack.push(rm.clone());
+        rm
+    }
+}
+
+/// A delay line pitch shifter. Two taps sweep through a window of `window`
+/// seconds at the speed that resamples the input by the shift of `semitones`
+/// plus `cents`, each jumping back when it reaches the end of the window. The
+/// taps are half a window apart and crossfaded with Hann windows so the jumps
+/// are silent. Longer windows smear transients less audibly on sustained
+/// sounds, shorter ones suit percussive input. `mix` fades from the dry input
+/// at 0 to the shifted signal at 1.
+#[derive(Debug, Copy, Clone)]
+pub struct PitchShift {
+    tag: Tag,
+    wave: Tag,
+}
+
+impl PitchShift {
+    pub fn new<T: Into<Tag>>(tag: T, wave: Tag) -> Self {
+        Self {
+            tag: tag.into(),
+            wave,
+        }
+    }
+
+    props!(semitones, set_semitones, 0);
+    props!(cents, set_cents, 1);
+    props!(window, set_window, 2);
+    props!(mix, set_mix, 3);
+
+    /// The ratio of output to input frequency.
+    pub fn ratio(&self, rack: &Rack) -> f32 {
+        2f32.powf((100.0 * self.semitones(rack) + self.cents(rack)) / 1200.0)
+    }
+}
+
+impl Signal for PitchShift {
+    tag!();
+    fn signal(&self, rack: &mut Rack, sample_rate: f32) {
+        let input = rack.outputs[(self.wave, 0)];
+        let mix = self.mix(rack).clamp(0.0, 1.0);
+        let buffer_len = rack.buffers.buffers(self.tag).len() as f32;
+        let window = (self.window(rack) * sample_rate).clamp(4.0, buffer_len - 4.0);
+        // The delay shrinks by `ratio - 1` samples per sample for a shift up
+        // and grows for a shift down.
+        let hz = (1.0 - self.ratio(rack)) * sample_rate / window;
+        let phase = lfo_step(rack, self.tag, hz, sample_rate);
+
+        let buffer = rack.buffers.buffers_mut(self.tag);
+        buffer.push(input);
+        let mut wet = 0.0;
+        for k in 0..2 {
+            let p = (phase + 0.5 * k as f32).fract();
+            let gain = (PI * p).sin().powi(2);
+            wet += gain * buffer.get_cubic(1.0 + p * window);
+        }
+        rack.outputs[(self.tag, 0)] = (1.0 - mix) * input + mix * wet;
+    }
+}
+
+#[derive(Debug, Copy, Clone)]
+pub struct PitchShiftBuilder {
+    wave: Tag,
+    max_window: f32,
+    max_sample_rate: f32,
+    semitones: Control,
+    cents: Control,
+    window: Control,
+    mix: Control,
+}
+
+impl PitchShiftBuilder {
+    pub fn new(wave: Tag) -> Self {
+        Self {
+            wave,
+            max_window: 0.2,
+            max_sample_rate: MAX_SAMPLE_RATE,
+            semitones: 0.0.into(),
+            cents: 0.0.into(),
+            window: 0.05.into(),
+            mix: 1.0.into(),
+        }
+    }
+
+    build!(semitones);
+    build!(cents);
+    build!(window);
+    build!(mix);
+
+    /// The longest window in seconds, defaults to 0.2.
+    pub fn max_window(&mut self, value: f32) -> &mut Self {
+        self.max_window = value;
+        self
+    }
+
+    /// The highest sample rate the delay line is sized for, defaults to
+    /// [`MAX_SAMPLE_RATE`].
+    pub fn max_sample_rate(&mut self, value: f32) -> &mut Self {
+        self.max_sample_rate = value;
+        self
+    }
+
+    pub fn rack(&self, rack: &mut Rack) -> Arc<PitchShift> {
+        let n = rack.num_modules();
+        rack.controls[(n, 0)] = self.semitones;
+        rack.controls[(n, 1)] = self.cents;
+        rack.controls[(n, 2)] = self.window;
+        rack.controls[(n, 3)] = self.mix;
+        let ps = Arc::new(PitchShift::new(n, self.wave));
+        rack.buffers
+            .set_buffer(ps.tag, delay_buffer(self.max_window, self.max_sample_rate));
+        rack.push(ps.clone());
+        ps
+    }
+}
@@ -3,6 +3,13 @@ use crate::utils::{interp, interp_inv};
 use crate::{build, props, tag};
 use std::sync::Arc;
 
+/// Attack, decay, sustain, release envelope. It is triggered either with
+/// `on`/`off` or by the `gate` input: a rising edge (the gate going above 0)
+/// starts the attack from the current level and a falling edge starts the
+/// release, so a clock, sequencer or comparator can drive it from the patch.
+/// The release starts from the level the envelope is at when it is turned off,
+/// even during the attack or decay. `ax`, `dx` and `rx` shape the attack, decay
+/// and release curves.
 #[derive(Copy, Clone, Debug)]
 pub struct Adsr {
     tag: Tag,
@@ -25,6 +32,7 @@ impl Adsr {
     props!(decay, set_decay, 1);
     props!(sustain, set_sustain, 2);
     props!(release, set_release, 3);
+    props!(gate, set_gate, 5);
 
     pub fn triggered(&self, rack: &Rack) -> bool {
         let ctrl = rack.controls[(self.tag, 4)];
@@ -41,8 +49,11 @@ impl Adsr {
     pub fn on(&self, rack: &mut Rack) {
         self.set_triggered(rack, true);
         rack.state[(self.tag, 1)] = 0.0;
+        // Start the attack from the current level, `interp_inv` gives the
+        // fraction of the attack that level is reached at.
         let x = rack.state[(self.tag, 2)];
-        rack.state[(self.tag, 0)] = interp_inv(0.0, 1.0 - self.ax, 1.0, x);
+        let a = self.attack(rack).max(0.005);
+        rack.state[(self.tag, 0)] = a * interp_inv(0.0, 1.0 - self.ax, 1.0, x);
     }
 
     pub fn off(&self, rack: &mut Rack) {
@@ -57,24 +68,43 @@ impl Signal for Adsr {
         let d = self.decay(rack).max(0.005);
         let s = self.sustain(rack);
         let r = self.release(rack).max(0.005);
+        // state: 0 - time since the attack started, 1 - time since the
+        // release started, 2 - current value, 3 - previous gate, 4 - level at
+        // the start of the release, 5 - 1.0 if triggered on the previous
+        // sample.
+        let tag = self.tag;
+        let gate = self.gate(rack);
+        let previous = rack.state[(tag, 3)];
+        if gate > 0.0 && previous <= 0.0 {
+            self.on(rack);
+        } else if gate <= 0.0 && previous > 0.0 {
+            self.off(rack);
+        }
+        rack.state[(tag, 3)] = gate;
         let triggered = self.triggered(rack);
-        rack.state[(self.tag, 2)] = match (triggered, rack.state[(self.tag, 0)]) {
-            (_, t) if t < a => interp(0.0, 1.0 - self.ax, 1.0, t / a),
-            (_, t) if t < a + d => interp(1.0, s + self.dx * (1.0 - s), s, (t - a) / d),
-            (true, t) => {
-                rack.state[(self.tag, 1)] = t - a - d;
-                s
+        if !triggered && rack.state[(tag, 5)] > 0.0 {
+            rack.state[(tag, 1)] = 0.0;
+            rack.state[(tag, 4)] = rack.state[(tag, 2)];
+        }
+        rack.state[(tag, 5)] = if triggered { 1.0 } else { 0.0 };
+        rack.state[(tag, 2)] = if triggered {
+            match rack.state[(tag, 0)] {
+                t if t < a => interp(0.0, 1.0 - self.ax, 1.0, t / a),
+                t if t < a + d => interp(1.0, s + self.dx * (1.0 - s), s, (t - a) / d),
+                _ => s,
+            }
+        } else {
+            let level = rack.state[(tag, 4)];
+            let t = rack.state[(tag, 1)];
+            rack.state[(tag, 1)] += 1.0 / sample_rate;
+            if t < r {
+                interp(level, self.rx * level, 0.0, t / r)
+            } else {
+                0.0
             }
-            (false, t) if t < a + d + r + rack.state[(self.tag, 1)] => interp(
-                s,
-                self.rx * s,
-                0.0,
-                t - a - d - rack.state[(self.tag, 1)] / r,
-            ),
-            (false, _) => 0.0,
         };
-        rack.outputs[(self.tag, 0)] = rack.state[(self.tag, 2)];
-        rack.state[(self.tag, 0)] += 1.0 / sample_rate;
+        rack.outputs[(tag, 0)] = rack.state[(tag, 2)];
+        rack.state[(tag, 0)] += 1.0 / sample_rate;
     }
 }
 
@@ -88,6 +118,7 @@ pub struct AdsrBuilder {
     sustain: Control,
     release: Control,
     triggered: Control,
+    gate: Control,
 }
 
 impl Default for AdsrBuilder {
@@ -97,6 +128,7 @@ impl Default for AdsrBuilder {
         let sustain = 1.0.into();
         let release = 0.1.into();
         let triggered = false.into();
+        let gate = 0.0.into();
         Self {
             ax: 0.5,
             dx: 0.5,
@@ -106,6 +138,7 @@ impl Default for AdsrBuilder {
             sustain,
             release,
             triggered,
+            gate,
         }
     }
 }
@@ -132,6 +165,7 @@ impl AdsrBuilder {
     build!(decay);
     build!(sustain);
     build!(release);
+    build!(gate);
 
     pub fn ax(&mut self, value: f32) -> &mut Self {
         self.ax = value;
@@ -156,6 +190,7 @@ impl AdsrBuilder {
         rack.controls[(n, 2)] = self.sustain;
         rack.controls[(n, 3)] = self.release;
         rack.controls[(n, 4)] = self.triggered;
+        rack.controls[(n, 5)] = self.gate;
         let adsr = Arc::new(Adsr::new(n, self.ax, self.dx, self.rx));
         rack.push(adsr.clone());
         adsr
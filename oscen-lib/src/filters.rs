@@ -1,8 +1,49 @@
 use crate::rack::*;
+use crate::utils::log_frequencies;
 use crate::{build, props, tag};
 use std::f32::consts::PI;
 use std::sync::Arc;
 
+/// Coefficients cached in module state. `cache` holds a flag that is 1.0 once
+/// the cache is filled, the `P` parameters the coefficients were computed from,
+/// and the `C` coefficients. `f` is only called when `params` change, so
+/// filters with constant (or slowly changing) controls skip the trigonometry.
+pub(crate) fn cached<const P: usize, const C: usize>(
+    cache: &mut [f32],
+    params: [f32; P],
+    f: impl FnOnce() -> [f32; C],
+) -> [f32; C] {
+    if cache[0] != 1.0 || cache[1..=P] != params {
+        cache[0] = 1.0;
+        cache[1..=P].copy_from_slice(&params);
+        cache[P + 1..P + 1 + C].copy_from_slice(&f());
+    }
+    let mut coefficients = [0.0; C];
+    coefficients.copy_from_slice(&cache[P + 1..P + 1 + C]);
+    coefficients
+}
+
+// Shared by `Lpf`, `Hpf`, `Bpf` and `Notch`. `coefficients` maps the cutoff in
+// radians per sample to `[a0, a1, a2, b1, b2]`, which are cached in `state`
+// after the input and output history.
+fn classic_biquad(
+    state: &mut [f32],
+    x0: f32,
+    [cut_off, q]: [f32; 2],
+    coefficients: impl FnOnce(f32) -> [f32; 5],
+    sample_rate: f32,
+) -> f32 {
+    let [a0, a1, a2, b1, b2] = cached(&mut state[4..], [cut_off, q, sample_rate], || {
+        coefficients(2.0 * PI * cut_off / sample_rate)
+    });
+    let y0 = a0 * x0 + a1 * state[0] + a2 * state[1] - b1 * state[2] - b2 * state[3];
+    state[1] = state[0];
+    state[0] = x0;
+    state[3] = state[2];
+    state[2] = if y0.is_nan() { 0.0 } else { y0 };
+    y0
+}
+
 #[derive(Debug, Copy, Clone)]
 pub struct Lpf {
     tag: Tag,
@@ -36,24 +77,19 @@ impl Signal for Lpf {
             rack.outputs[(self.tag, 0)] = x0;
             return;
         }
-        let tag = self.tag;
         let q = self.q(rack);
-        let phi = 2.0 * PI * cut_off / sample_rate;
-        let b2 = (2.0 * q - phi.sin()) / (2.0 * q + phi.sin());
-        let b1 = -(1.0 + b2) * phi.cos();
-        let a0 = 0.25 * (1.0 + b1 + b2);
-        let a1 = 2.0 * a0;
-        rack.outputs[(tag, 0)] = a0 * x0 + a1 * rack.state[(tag, 0)] + a0 * rack.state[(tag, 1)]
-            - b1 * rack.state[(tag, 2)]
-            - b2 * rack.state[(tag, 3)];
-        rack.state[(tag, 1)] = rack.state[(tag, 0)];
-        rack.state[(tag, 0)] = x0;
-        rack.state[(tag, 3)] = rack.state[(tag, 2)];
-        rack.state[(tag, 2)] = if rack.outputs[(tag, 0)].is_nan() {
-            0.0
-        } else {
-            rack.outputs[(tag, 0)]
-        };
+        rack.outputs[(self.tag, 0)] = classic_biquad(
+            rack.state.state_mut(self.tag),
+            x0,
+            [cut_off, q],
+            |phi| {
+                let b2 = (2.0 * q - phi.sin()) / (2.0 * q + phi.sin());
+                let b1 = -(1.0 + b2) * phi.cos();
+                let a0 = 0.25 * (1.0 + b1 + b2);
+                [a0, 2.0 * a0, a0, b1, b2]
+            },
+            sample_rate,
+        );
     }
 }
 
@@ -123,24 +159,14 @@ impl Signal for Hpf {
             rack.outputs[(self.tag, 0)] = x0;
             return;
         }
-        let tag = self.tag;
         let q = self.q(rack);
-        let phi = 2.0 * PI * cut_off / sample_rate;
-        let b2 = (2.0 * q - phi.sin()) / (2.0 * q + phi.sin());
-        let b1 = -(1.0 + b2) * phi.cos();
-        let a0 = 0.25 * (1.0 - b1 + b2);
-        let a1 = -2.0 * a0;
-        rack.outputs[(tag, 0)] = a0 * x0 + a1 * rack.state[(tag, 0)] + a0 * rack.state[(tag, 1)]
-            - b1 * rack.state[(tag, 2)]
-            - b2 * rack.state[(tag, 3)];
-        rack.state[(tag, 1)] = rack.state[(tag, 0)];
-        rack.state[(tag, 0)] = x0;
-        rack.state[(tag, 3)] = rack.state[(tag, 2)];
-        rack.state[(tag, 2)] = if rack.outputs[(tag, 0)].is_nan() {
-            0.0
-        } else {
-            rack.outputs[(tag, 0)]
-        };
+        rack.outputs[(self.tag, 0)] = classic_biquad(
+            rack.state.state_mut(self.tag),
+            x0,
+            [cut_off, q],
+            |phi| hpf_coefficients(phi, q),
+            sample_rate,
+        );
     }
 }
 
@@ -200,6 +226,23 @@ impl Bpf {
     }
 }
 
+// Highpass, `phi` is the cutoff in radians per sample.
+fn hpf_coefficients(phi: f32, q: f32) -> [f32; 5] {
+    let b2 = (2.0 * q - phi.sin()) / (2.0 * q + phi.sin());
+    let b1 = -(1.0 + b2) * phi.cos();
+    let a0 = 0.25 * (1.0 - b1 + b2);
+    [a0, -2.0 * a0, a0, b1, b2]
+}
+
+// Bandpass with a peak gain of 1, `phi` is the centre frequency in radians per
+// sample.
+fn bpf_coefficients(phi: f32, q: f32) -> [f32; 5] {
+    let b2 = (PI / 4.0 - phi / (2.0 * q)).tan();
+    let b1 = -(1.0 + b2) * phi.cos();
+    let a0 = 0.5 * (1.0 - b2);
+    [a0, 0.0, -a0, b1, b2]
+}
+
 impl Signal for Bpf {
     tag!();
     fn signal(&self, rack: &mut Rack, sample_rate: f32) {
@@ -209,25 +252,14 @@ impl Signal for Bpf {
             rack.outputs[(self.tag, 0)] = x0;
             return;
         }
-        let tag = self.tag;
         let q = self.q(rack);
-        let phi = 2.0 * PI * cut_off / sample_rate;
-        let b2 = (PI / 4.0 - phi / (2.0 * q)).tan();
-        let b1 = -(1.0 + b2) * phi.cos();
-        let a0 = 0.5 * (1.0 - b2);
-        let a1 = 0.0;
-        let a2 = -a0;
-        rack.outputs[(tag, 0)] = a0 * x0 + a1 * rack.state[(tag, 0)] + a2 * rack.state[(tag, 1)]
-            - b1 * rack.state[(tag, 2)]
-            - b2 * rack.state[(tag, 3)];
-        rack.state[(tag, 1)] = rack.state[(tag, 0)];
-        rack.state[(tag, 0)] = x0;
-        rack.state[(tag, 3)] = rack.state[(tag, 2)];
-        rack.state[(tag, 2)] = if rack.outputs[(tag, 0)].is_nan() {
-            0.0
-        } else {
-            rack.outputs[(tag, 0)]
-        };
+        rack.outputs[(self.tag, 0)] = classic_biquad(
+            rack.state.state_mut(self.tag),
+            x0,
+            [cut_off, q],
+            |phi| bpf_coefficients(phi, q),
+            sample_rate,
+        );
     }
 }
 
@@ -296,24 +328,19 @@ impl Signal for Notch {
             rack.outputs[(self.tag, 0)] = x0;
             return;
         }
-        let tag = self.tag;
         let q = self.q(rack);
-        let phi = 2.0 * PI * cut_off / sample_rate;
-        let b2 = (PI / 4.0 - phi / (2.0 * q)).tan();
-        let b1 = -(1.0 + b2) * phi.cos();
-        let a0 = 0.5 * (1.0 + b2);
-        let a1 = b1;
-        rack.outputs[(tag, 0)] = a0 * x0 + a1 * rack.state[(tag, 0)] + a0 * rack.state[(tag, 1)]
-            - b1 * rack.state[(tag, 2)]
-            - b2 * rack.state[(tag, 3)];
-        rack.state[(tag, 1)] = rack.state[(tag, 0)];
-        rack.state[(tag, 0)] = x0;
-        rack.state[(tag, 3)] = rack.state[(tag, 2)];
-        rack.state[(tag, 2)] = if rack.outputs[(tag, 0)].is_nan() {
-            0.0
-        } else {
-            rack.outputs[(tag, 0)]
-        };
+        rack.outputs[(self.tag, 0)] = classic_biquad(
+            rack.state.state_mut(self.tag),
+            x0,
+            [cut_off, q],
+            |phi| {
+                let b2 = (PI / 4.0 - phi / (2.0 * q)).tan();
+                let b1 = -(1.0 + b2) * phi.cos();
+                let a0 = 0.5 * (1.0 + b2);
+                [a0, b1, a0, b1, b2]
+            },
+            sample_rate,
+        );
     }
 }
 
@@ -339,81 +366,1339 @@ impl NotchBuilder {
     build!(q);
     build!(off);
 
-    pub fn rack(&self, rack: &mut Rack, controls: &mut Controls) -> Arc<Notch> {
+    pub fn rack(&self, rack: &mut Rack) -> Arc<Notch> {
         let n = rack.num_modules();
-        controls[(n, 0)] = self.cut_off;
-        controls[(n, 1)] = self.q;
-        controls[(n, 2)] = self.off;
+        rack.controls[(n, 0)] = self.cut_off;
+        rack.controls[(n, 1)] = self.q;
+        rack.controls[(n, 2)] = self.off;
         let notch = Arc::new(Notch::new(n.into(), self.wave));
         rack.push(notch.clone());
         notch
     }
 }
+/// Zero-delay-feedback state variable filter using the topology preserving
+/// transform. Unlike the biquads it stays stable when `cutoff` is modulated at
+/// audio rate. All five responses are computed at once: lowpass, highpass,
+/// bandpass, notch and peak are written to output slots 0 - 4.
+#[derive(Debug, Copy, Clone)]
+pub struct Svf {
+    tag: Tag,
+    wave: Tag,
+}
+
+impl Svf {
+    pub const LOWPASS: usize = 0;
+    pub const HIGHPASS: usize = 1;
+    pub const BANDPASS: usize = 2;
+    pub const NOTCH: usize = 3;
+    pub const PEAK: usize = 4;
+
+    pub fn new(tag: Tag, wave: Tag) -> Self {
+        Self { tag, wave }
+    }
+    props!(cutoff, set_cutoff, 0);
+    props!(q, set_q, 1);
+    pub fn off(&self, rack: &Rack) -> bool {
+        let ctrl = rack.controls[(self.tag, 2)];
+        match ctrl {
+            Control::B(b) => b,
+            _ => panic!("off must be a bool, not {ctrl:?}"),
+        }
+    }
+    pub fn set_off(&self, rack: &mut Rack, value: bool) {
+        rack.controls[(self.tag, 2)] = value.into();
+    }
+}
+
+impl Signal for Svf {
+    tag!();
+    fn signal(&self, rack: &mut Rack, sample_rate: f32) {
+        let tag = self.tag;
+        let v0 = rack.outputs[(self.wave, 0)];
+        if self.off(rack) {
+            rack.outputs.outputs_mut(tag)[0..5].fill(v0);
+            return;
+        }
+        let cut_off = self.cutoff(rack);
+        let q = self.q(rack);
+        // state: 0, 1 - the integrators' equivalent currents, 2 - 9 cached
+        // coefficients.
+        let [k, a1, a2, a3] = cached(
+            &mut rack.state.state_mut(tag)[2..],
+            [cut_off, q, sample_rate],
+            || {
+                let g = (PI * cut_off.clamp(1.0, 0.49 * sample_rate) / sample_rate).tan();
+                let k = 1.0 / q.max(0.01);
+                let a1 = 1.0 / (1.0 + g * (g + k));
+                [k, a1, g * a1, g * g * a1]
+            },
+        );
+        let ic1eq = rack.state[(tag, 0)];
+        let ic2eq = rack.state[(tag, 1)];
+        let v3 = v0 - ic2eq;
+        let v1 = a1 * ic1eq + a2 * v3;
+        let v2 = ic2eq + a2 * ic1eq + a3 * v3;
+        rack.state[(tag, 0)] = 2.0 * v1 - ic1eq;
+        rack.state[(tag, 1)] = 2.0 * v2 - ic2eq;
+        let low = v2;
+        let band = v1;
+        let high = v0 - k * v1 - v2;
+        let out = rack.outputs.outputs_mut(tag);
+        out[Self::LOWPASS] = low;
+        out[Self::HIGHPASS] = high;
+        out[Self::BANDPASS] = band;
+        out[Self::NOTCH] = low + high;
+        out[Self::PEAK] = low - high;
+    }
+}
+
+#[derive(Debug, Copy, Clone)]
+pub struct SvfBuilder {
+    wave: Tag,
+    cut_off: Control,
+    q: Control,
+    off: Control,
+}
+
+impl SvfBuilder {
+    pub fn new(wave: Tag) -> Self {
+        Self {
+            wave,
+            cut_off: 1_000.0.into(),
+            q: 0.707.into(),
+            off: false.into(),
+        }
+    }
+
+    build!(cut_off);
+    build!(q);
+    build!(off);
+
+    pub fn rack(&self, rack: &mut Rack) -> Arc<Svf> {
+        let n = rack.num_modules();
+        rack.controls[(n, 0)] = self.cut_off;
+        rack.controls[(n, 1)] = self.q;
+        rack.controls[(n, 2)] = self.off;
+        let svf = Arc::new(Svf::new(n.into(), self.wave));
+        rack.push(svf.clone());
+        svf
+    }
+}
+/// Moog-style 4 pole ladder lowpass filter. Each stage is a zero-delay-feedback
+/// one pole and the input passes through a `tanh` saturator scaled by `drive`.
+/// `resonance` of 1.0 is the edge of self-oscillation. The output is taken after
+/// 2 or 4 poles (12 or 24 dB/oct) depending on `poles`, and is compensated for
+/// the drop in passband gain as the resonance increases.
+#[derive(Debug, Copy, Clone)]
+pub struct Ladder {
+    tag: Tag,
+    wave: Tag,
+}
+
+impl Ladder {
+    pub fn new(tag: Tag, wave: Tag) -> Self {
+        Self { tag, wave }
+    }
+    props!(cutoff, set_cutoff, 0);
+    props!(resonance, set_resonance, 1);
+    props!(drive, set_drive, 2);
+    pub fn poles(&self, rack: &Rack) -> usize {
+        let inp = rack.controls[(self.tag, 3)];
+        rack.outputs.integer(inp).expect("poles must be Control::I")
+    }
+    pub fn set_poles(&self, rack: &mut Rack, value: usize) {
+        rack.controls[(self.tag, 3)] = value.into();
+    }
+}
+
+impl Signal for Ladder {
+    tag!();
+    fn signal(&self, rack: &mut Rack, sample_rate: f32) {
+        let tag = self.tag;
+        let x = self.drive(rack) * rack.outputs[(self.wave, 0)];
+        let cut_off = self.cutoff(rack);
+        let k = 4.0 * self.resonance(rack).max(0.0);
+        // state: 0 - 3 the integrator state of each stage, 4 - 7 the cached
+        // one pole gain.
+        let [big_g] = cached(
+            &mut rack.state.state_mut(tag)[4..],
+            [cut_off, sample_rate],
+            || {
+                let g = (PI * cut_off.clamp(1.0, 0.49 * sample_rate) / sample_rate).tan();
+                [g / (1.0 + g)]
+            },
+        );
+        let s = &mut rack.state.state_mut(tag)[0..4];
+        let sigma = s
+            .iter()
+            .fold(0.0, |acc, si| acc * big_g + si * (1.0 - big_g));
+        let y4 = (big_g.powi(4) * x + sigma) / (1.0 + k * big_g.powi(4));
+        let mut y = (x - k * y4).tanh();
+        let mut ys = [0.0; 4];
+        for (si, yi) in s.iter_mut().zip(ys.iter_mut()) {
+            let v = (y - *si) * big_g;
+            y = v + *si;
+            *si = y + v;
+            *yi = y;
+        }
+        if s.iter().any(|si| !si.is_finite()) {
+            s.fill(0.0);
+        }
+        let out = if self.poles(rack) == 2 { ys[1] } else { ys[3] };
+        rack.outputs[(tag, 0)] = (1.0 + k) * out;
+    }
+}
+
+#[derive(Debug, Copy, Clone)]
+pub struct LadderBuilder {
+    wave: Tag,
+    cut_off: Control,
+    resonance: Control,
+    drive: Control,
+    poles: Control,
+}
+
+impl LadderBuilder {
+    pub fn new(wave: Tag) -> Self {
+        Self {
+            wave,
+            cut_off: 1_000.0.into(),
+            resonance: 0.0.into(),
+            drive: 1.0.into(),
+            poles: 4.into(),
+        }
+    }
+
+    build!(cut_off);
+    build!(resonance);
+    build!(drive);
+    build!(poles);
+
+    pub fn rack(&self, rack: &mut Rack) -> Arc<Ladder> {
+        let n = rack.num_modules();
+        rack.controls[(n, 0)] = self.cut_off;
+        rack.controls[(n, 1)] = self.resonance;
+        rack.controls[(n, 2)] = self.drive;
+        rack.controls[(n, 3)] = self.poles;
+        let ladder = Arc::new(Ladder::new(n.into(), self.wave));
+        rack.push(ladder.clone());
+        ladder
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum BiquadType {
+    Lowpass,
+    Highpass,
+    Bandpass,
+    Notch,
+    Allpass,
+    Peaking,
+    LowShelf,
+    HighShelf,
+}
+
+impl From<BiquadType> for Control {
+    fn from(kind: BiquadType) -> Self {
+        Control::I(kind as usize)
+    }
+}
+
+impl From<usize> for BiquadType {
+    fn from(u: usize) -> Self {
+        match u {
+            0 => BiquadType::Lowpass,
+            1 => BiquadType::Highpass,
+            2 => BiquadType::Bandpass,
+            3 => BiquadType::Notch,
+            4 => BiquadType::Allpass,
+            5 => BiquadType::Peaking,
+            6 => BiquadType::LowShelf,
+            7 => BiquadType::HighShelf,
+            _ => panic!("No BiquadType with index {u}"),
+        }
+    }
+}
+
+/// Normalized biquad coefficients, i.e. `a0` is 1.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct BiquadCoefficients {
+    pub b0: f32,
+    pub b1: f32,
+    pub b2: f32,
+    pub a1: f32,
+    pub a2: f32,
+}
+
+impl BiquadCoefficients {
+    /// The coefficients from Robert Bristow-Johnson's *Audio EQ Cookbook*.
+    /// `gain` in dB is only used by the peaking and shelf types.
+    pub fn new(kind: BiquadType, cutoff: f32, q: f32, gain: f32, sample_rate: f32) -> Self {
+        let w0 = 2.0 * PI * cutoff.clamp(1.0, 0.49 * sample_rate) / sample_rate;
+        let (sin, cos) = w0.sin_cos();
+        let alpha = sin / (2.0 * q.max(0.01));
+        let a = 10f32.powf(gain / 40.0);
+        let sq = 2.0 * a.sqrt() * alpha;
+        let (b0, b1, b2, a0, a1, a2) = match kind {
+            BiquadType::Lowpass => {
+                let b = (1.0 - cos) / 2.0;
+                (b, 2.0 * b, b, 1.0 + alpha, -2.0 * cos, 1.0 - alpha)
+            }
+            BiquadType::Highpass => {
+                let b = (1.0 + cos) / 2.0;
+                (b, -2.0 * b, b, 1.0 + alpha, -2.0 * cos, 1.0 - alpha)
+            }
+            BiquadType::Bandpass => (alpha, 0.0, -alpha, 1.0 + alpha, -2.0 * cos, 1.0 - alpha),
+            BiquadType::Notch => (1.0, -2.0 * cos, 1.0, 1.0 + alpha, -2.0 * cos, 1.0 - alpha),
+            BiquadType::Allpass => (
+                1.0 - alpha,
+                -2.0 * cos,
+                1.0 + alpha,
+                1.0 + alpha,
+                -2.0 * cos,
+                1.0 - alpha,
+            ),
+            BiquadType::Peaking => (
+                1.0 + alpha * a,
+                -2.0 * cos,
+                1.0 - alpha * a,
+                1.0 + alpha / a,
+                -2.0 * cos,
+                1.0 - alpha / a,
+            ),
+            BiquadType::LowShelf => (
+                a * ((a + 1.0) - (a - 1.0) * cos + sq),
+                2.0 * a * ((a - 1.0) - (a + 1.0) * cos),
+                a * ((a + 1.0) - (a - 1.0) * cos - sq),
+                (a + 1.0) + (a - 1.0) * cos + sq,
+                -2.0 * ((a - 1.0) + (a + 1.0) * cos),
+                (a + 1.0) + (a - 1.0) * cos - sq,
+            ),
+            BiquadType::HighShelf => (
+                a * ((a + 1.0) + (a - 1.0) * cos + sq),
+                -2.0 * a * ((a - 1.0) + (a + 1.0) * cos),
+                a * ((a + 1.0) + (a - 1.0) * cos - sq),
+                (a + 1.0) - (a - 1.0) * cos + sq,
+                2.0 * ((a - 1.0) - (a + 1.0) * cos),
+                (a + 1.0) - (a - 1.0) * cos - sq,
+            ),
+        };
+        Self {
+            b0: b0 / a0,
+            b1: b1 / a0,
+            b2: b2 / a0,
+            a1: a1 / a0,
+            a2: a2 / a0,
+        }
+    }
+
+    fn to_array(self) -> [f32; 5] {
+        [self.b0, self.b1, self.b2, self.a1, self.a2]
+    }
+
+    fn from_array([b0, b1, b2, a1, a2]: [f32; 5]) -> Self {
+        Self { b0, b1, b2, a1, a2 }
+    }
+
+    /// Filter one sample in direct form I. `state` holds the previous two
+    /// inputs followed by the previous two outputs.
+    pub fn process(&self, x0: f32, state: &mut [f32]) -> f32 {
+        let y0 = self.b0 * x0 + self.b1 * state[0] + self.b2 * state[1]
+            - self.a1 * state[2]
+            - self.a2 * state[3];
+        state[1] = state[0];
+        state[0] = x0;
+        state[3] = state[2];
+        state[2] = if y0.is_finite() { y0 } else { 0.0 };
+        y0
+    }
+
+    /// The magnitude (as a linear gain) and phase (in radians) of the filter at
+    /// `hz`.
+    pub fn response(&self, hz: f32, sample_rate: f32) -> (f32, f32) {
+        let w = 2.0 * PI * hz / sample_rate;
+        let (s1, c1) = (-w).sin_cos();
+        let (s2, c2) = (-2.0 * w).sin_cos();
+        let num = (
+            self.b0 + self.b1 * c1 + self.b2 * c2,
+            self.b1 * s1 + self.b2 * s2,
+        );
+        let den = (
+            1.0 + self.a1 * c1 + self.a2 * c2,
+            self.a1 * s1 + self.a2 * s2,
+        );
+        let mag = (num.0.hypot(num.1)) / den.0.hypot(den.1);
+        let phase = num.1.atan2(num.0) - den.1.atan2(den.0);
+        (mag, phase)
+    }
+}
+
+// Length of the crossfade when the type of a `Biquad` changes.
+const BIQUAD_FADE: f32 = 0.01;
+
+/// A general second order filter whose type can be switched while running.
+/// When the type changes the old and new responses are crossfaded over 10 ms
+/// so that the switch does not click. `gain` is in dB and only affects the
+/// peaking and shelf types.
+#[derive(Debug, Copy, Clone)]
+pub struct Biquad {
+    tag: Tag,
+    wave: Tag,
+}
+
+impl Biquad {
+    pub fn new(tag: Tag, wave: Tag) -> Self {
+        Self { tag, wave }
+    }
+    props!(cutoff, set_cutoff, 0);
+    props!(q, set_q, 1);
+    props!(gain, set_gain, 2);
+    pub fn kind(&self, rack: &Rack) -> BiquadType {
+        let inp = rack.controls[(self.tag, 3)];
+        rack.outputs
+            .integer(inp)
+            .expect("kind must be Control::I")
+            .into()
+    }
+    pub fn set_kind(&self, rack: &mut Rack, value: BiquadType) {
+        rack.controls[(self.tag, 3)] = value.into();
+    }
+}
+
+impl Signal for Biquad {
+    tag!();
+    fn signal(&self, rack: &mut Rack, sample_rate: f32) {
+        let tag = self.tag;
+        let x0 = rack.outputs[(self.wave, 0)];
+        let kind = self.kind(rack);
+        let cutoff = self.cutoff(rack);
+        let q = self.q(rack);
+        let gain = self.gain(rack);
+        // state: 0 - 3 history of the current type, 4 - 7 history of the
+        // previous type, 8 - current type, 9 - previous type, 10 - crossfade
+        // remaining, 11 - 21 cached coefficients of the current type.
+        let state = rack.state.state_mut(tag);
+        if kind as usize as f32 != state[8] {
+            let (current, previous) = state.split_at_mut(4);
+            previous[0..4].copy_from_slice(current);
+            state[9] = state[8];
+            state[8] = kind as usize as f32;
+            state[10] = 1.0;
+        }
+        let coefficients = cached(
+            &mut state[11..],
+            [kind as usize as f32, cutoff, q, gain, sample_rate],
+            || BiquadCoefficients::new(kind, cutoff, q, gain, sample_rate).to_array(),
+        );
+        let y = BiquadCoefficients::from_array(coefficients).process(x0, &mut state[0..4]);
+        let fade = state[10];
+        rack.outputs[(tag, 0)] = if fade > 0.0 {
+            let old = BiquadType::from(state[9] as usize);
+            let y_old = BiquadCoefficients::new(old, cutoff, q, gain, sample_rate)
+                .process(x0, &mut state[4..8]);
+            state[10] = (fade - 1.0 / (BIQUAD_FADE * sample_rate)).max(0.0);
+            fade * y_old + (1.0 - fade) * y
+        } else {
+            y
+        };
+    }
+}
+
+#[derive(Debug, Copy, Clone)]
+pub struct BiquadBuilder {
+    wave: Tag,
+    cut_off: Control,
+    q: Control,
+    gain: Control,
+    kind: Control,
+}
+
+impl BiquadBuilder {
+    pub fn new(wave: Tag, kind: BiquadType) -> Self {
+        Self {
+            wave,
+            cut_off: 1_000.0.into(),
+            q: 0.707.into(),
+            gain: 0.0.into(),
+            kind: kind.into(),
+        }
+    }
+
+    build!(cut_off);
+    build!(q);
+    build!(gain);
+    build!(kind);
+
+    pub fn rack(&self, rack: &mut Rack) -> Arc<Biquad> {
+        let n = rack.num_modules();
+        rack.controls[(n, 0)] = self.cut_off;
+        rack.controls[(n, 1)] = self.q;
+        rack.controls[(n, 2)] = self.gain;
+        rack.controls[(n, 3)] = self.kind;
+        let biquad = Arc::new(Biquad::new(n.into(), self.wave));
+        rack.state[(n, 8)] = biquad.kind(rack) as usize as f32;
+        rack.push(biquad.clone());
+        biquad
+    }
+}
+
+/// One band of an [`Equalizer`]. Low and high cuts are `Highpass` and `Lowpass`
+/// bands, the other useful types are `Peaking`, `LowShelf` and `HighShelf`.
+#[derive(Debug, Copy, Clone)]
+pub struct EqBand {
+    kind: BiquadType,
+    hz: Control,
+    gain: Control,
+    q: Control,
+}
+
+/// A multi-band parametric equalizer, a series of biquads. Each band has an
+/// `hz`, `gain` (in dB) and `q` control.
+#[derive(Debug, Clone)]
+pub struct Equalizer {
+    tag: Tag,
+    wave: Tag,
+    bands: Vec<BiquadType>,
+}
+
+impl Equalizer {
+    pub fn new(tag: Tag, wave: Tag, bands: Vec<BiquadType>) -> Self {
+        assert!(
+            bands.len() <= MAX_CONTROLS / 3,
+            "Max number of equalizer bands is {}",
+            MAX_CONTROLS / 3
+        );
+        Self { tag, wave, bands }
+    }
+    pub fn bands(&self) -> &[BiquadType] {
+        &self.bands
+    }
+    pub fn hz(&self, rack: &Rack, band: usize) -> f32 {
+        let inp = rack.controls[(self.tag, 3 * band)];
+        rack.outputs.value(inp).unwrap()
+    }
+    pub fn set_hz(&self, rack: &mut Rack, band: usize, value: Control) {
+        rack.controls[(self.tag, 3 * band)] = value;
+    }
+    pub fn gain(&self, rack: &Rack, band: usize) -> f32 {
+        let inp = rack.controls[(self.tag, 3 * band + 1)];
+        rack.outputs.value(inp).unwrap()
+    }
+    pub fn set_gain(&self, rack: &mut Rack, band: usize, value: Control) {
+        rack.controls[(self.tag, 3 * band + 1)] = value;
+    }
+    pub fn q(&self, rack: &Rack, band: usize) -> f32 {
+        let inp = rack.controls[(self.tag, 3 * band + 2)];
+        rack.outputs.value(inp).unwrap()
+    }
+    pub fn set_q(&self, rack: &mut Rack, band: usize, value: Control) {
+        rack.controls[(self.tag, 3 * band + 2)] = value;
+    }
+
+    fn coefficients(&self, rack: &Rack, band: usize, sample_rate: f32) -> BiquadCoefficients {
+        BiquadCoefficients::new(
+            self.bands[band],
+            self.hz(rack, band),
+            self.q(rack, band),
+            self.gain(rack, band),
+            sample_rate,
+        )
+    }
+
+    /// The gain in dB of all the bands combined at `hz`, using the current
+    /// values of the controls.
+    pub fn magnitude(&self, rack: &Rack, hz: f32, sample_rate: f32) -> f32 {
+        (0..self.bands.len())
+            .map(|b| {
+                20.0 * self
+                    .coefficients(rack, b, sample_rate)
+                    .response(hz, sample_rate)
+                    .0
+                    .log10()
+            })
+            .sum()
+    }
+
+    /// The combined magnitude response at `points` logarithmically spaced
+    /// frequencies from 20 Hz to 20 kHz, as (Hz, dB) pairs for drawing.
+    pub fn magnitude_response(
+        &self,
+        rack: &Rack,
+        sample_rate: f32,
+        points: usize,
+    ) -> Vec<(f32, f32)> {
+        log_frequencies(20.0, 20_000f32.min(0.5 * sample_rate), points)
+            .into_iter()
+            .map(|hz| (hz, self.magnitude(rack, hz, sample_rate)))
+            .collect()
+    }
+}
+
+// Each band occupies `EQ_STRIDE` slots of the equalizer's buffer: 4 for the
+// input and output history and 10 for the cached coefficients.
+const EQ_STRIDE: usize = 14;
+
+impl Signal for Equalizer {
+    tag!();
+    fn signal(&self, rack: &mut Rack, sample_rate: f32) {
+        let mut y = rack.outputs[(self.wave, 0)];
+        for (b, kind) in self.bands.iter().enumerate() {
+            let (hz, q, gain) = (self.hz(rack, b), self.q(rack, b), self.gain(rack, b));
+            let state = &mut rack.buffers.buffers_mut(self.tag).as_mut_slice()
+                [b * EQ_STRIDE..(b + 1) * EQ_STRIDE];
+            let coefficients = cached(&mut state[4..], [hz, q, gain, sample_rate], || {
+                BiquadCoefficients::new(*kind, hz, q, gain, sample_rate).to_array()
+            });
+            y = BiquadCoefficients::from_array(coefficients).process(y, &mut state[0..4]);
+        }
+        rack.outputs[(self.tag, 0)] = y;
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct EqualizerBuilder {
+    wave: Tag,
+    bands: Vec<EqBand>,
+}
+
+impl EqualizerBuilder {
+    pub fn new(wave: Tag) -> Self {
+        Self {
+            wave,
+            bands: vec![],
+        }
+    }
+
+    /// Add a band, `gain` is in dB and ignored by the cut, bandpass and notch
+    /// types.
+    pub fn band<T, U, V>(&mut self, kind: BiquadType, hz: T, gain: U, q: V) -> &mut Self
+    where
+        T: Into<Control>,
+        U: Into<Control>,
+        V: Into<Control>,
+    {
+        assert!(
+            self.bands.len() < MAX_CONTROLS / 3,
+            "Max number of equalizer bands is {}",
+            MAX_CONTROLS / 3
+        );
+        self.bands.push(EqBand {
+            kind,
+            hz: hz.into(),
+            gain: gain.into(),
+            q: q.into(),
+        });
+        self
+    }
+
+    pub fn rack(&self, rack: &mut Rack) -> Arc<Equalizer> {
+        let n = rack.num_modules();
+        for (i, band) in self.bands.iter().enumerate() {
+            rack.controls[(n, 3 * i)] = band.hz;
+            rack.controls[(n, 3 * i + 1)] = band.gain;
+            rack.controls[(n, 3 * i + 2)] = band.q;
+        }
+        let kinds = self.bands.iter().map(|b| b.kind).collect();
+        let eq = Arc::new(Equalizer::new(n.into(), self.wave, kinds));
+        rack.buffers.set_buffer(
+            eq.tag,
+            RingBuffer::new(0, vec![0.0; self.bands.len() * EQ_STRIDE]),
+        );
+        rack.push(eq.clone());
+        eq
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum VoiceType {
+    Soprano,
+    Alto,
+    CounterTenor,
+    Tenor,
+    Bass,
+}
+
+impl From<VoiceType> for Control {
+    fn from(voice: VoiceType) -> Self {
+        Control::I(voice as usize)
+    }
+}
+
+impl From<usize> for VoiceType {
+    fn from(u: usize) -> Self {
+        match u {
+            0 => VoiceType::Soprano,
+            1 => VoiceType::Alto,
+            2 => VoiceType::CounterTenor,
+            3 => VoiceType::Tenor,
+            4 => VoiceType::Bass,
+            _ => panic!("No VoiceType with index {u}"),
+        }
+    }
+}
+
+const NUM_FORMANTS: usize = 5;
+
+// (frequencies in Hz, amplitudes in dB, bandwidths in Hz) of the vowels A, E,
+// I, O and U for each voice type, from the Csound manual's formant table.
+type Vowel = (
+    [f32; NUM_FORMANTS],
+    [f32; NUM_FORMANTS],
+    [f32; NUM_FORMANTS],
+);
+
+#[rustfmt::skip]
+const FORMANTS: [[Vowel; 5]; 5] = [
+    // Soprano
+    [
+        ([800.0, 1150.0, 2900.0, 3900.0, 4950.0], [0.0, -6.0, -32.0, -20.0, -50.0], [80.0, 90.0, 120.0, 130.0, 140.0]),
+        ([350.0, 2000.0, 2800.0, 3600.0, 4950.0], [0.0, -20.0, -15.0, -40.0, -56.0], [60.0, 100.0, 120.0, 150.0, 200.0]),
+        ([270.0, 2140.0, 2950.0, 3900.0, 4950.0], [0.0, -12.0, -26.0, -26.0, -44.0], [60.0, 90.0, 100.0, 120.0, 120.0]),
+        ([450.0, 800.0, 2830.0, 3800.0, 4950.0], [0.0, -11.0, -22.0, -22.0, -50.0], [70.0, 80.0, 100.0, 130.0, 135.0]),
+        ([325.0, 700.0, 2700.0, 3800.0, 4950.0], [0.0, -16.0, -35.0, -40.0, -60.0], [50.0, 60.0, 170.0, 180.0, 200.0]),
+    ],
+    // Alto
+    [
+        ([800.0, 1150.0, 2800.0, 3500.0, 4950.0], [0.0, -4.0, -20.0, -36.0, -60.0], [80.0, 90.0, 120.0, 130.0, 140.0]),
+        ([400.0, 1600.0, 2700.0, 3300.0, 4950.0], [0.0, -24.0, -30.0, -35.0, -60.0], [60.0, 80.0, 120.0, 150.0, 200.0]),
+        ([350.0, 1700.0, 2700.0, 3700.0, 4950.0], [0.0, -20.0, -30.0, -36.0, -60.0], [50.0, 100.0, 120.0, 150.0, 200.0]),
+        ([450.0, 800.0, 2830.0, 3500.0, 4950.0], [0.0, -9.0, -16.0, -28.0, -55.0], [70.0, 80.0, 100.0, 130.0, 135.0]),
+        ([325.0, 700.0, 2530.0, 3500.0, 4950.0], [0.0, -12.0, -30.0, -40.0, -64.0], [50.0, 60.0, 170.0, 180.0, 200.0]),
+    ],
+    // Counter tenor
+    [
+        ([660.0, 1120.0, 2750.0, 3000.0, 3350.0], [0.0, -6.0, -23.0, -24.0, -38.0], [80.0, 90.0, 120.0, 130.0, 140.0]),
+        ([440.0, 1800.0, 2700.0, 3000.0, 3300.0], [0.0, -14.0, -18.0, -20.0, -20.0], [70.0, 80.0, 100.0, 120.0, 120.0]),
+        ([270.0, 1850.0, 2900.0, 3350.0, 3590.0], [0.0, -24.0, -24.0, -36.0, -36.0], [40.0, 90.0, 100.0, 120.0, 120.0]),
+        ([430.0, 820.0, 2700.0, 3000.0, 3300.0], [0.0, -10.0, -26.0, -22.0, -34.0], [40.0, 80.0, 100.0, 120.0, 120.0]),
+        ([370.0, 630.0, 2750.0, 3000.0, 3400.0], [0.0, -20.0, -23.0, -30.0, -34.0], [40.0, 60.0, 100.0, 120.0, 120.0]),
+    ],
+    // Tenor
+    [
+        ([650.0, 1080.0, 2650.0, 2900.0, 3250.0], [0.0, -6.0, -7.0, -8.0, -22.0], [80.0, 90.0, 120.0, 130.0, 140.0]),
+        ([400.0, 1700.0, 2600.0, 3200.0, 3580.0], [0.0, -14.0, -12.0, -14.0, -20.0], [70.0, 80.0, 100.0, 120.0, 120.0]),
+        ([290.0, 1870.0, 2800.0, 3250.0, 3540.0], [0.0, -15.0, -18.0, -20.0, -30.0], [40.0, 90.0, 100.0, 120.0, 120.0]),
+        ([400.0, 800.0, 2600.0, 2800.0, 3000.0], [0.0, -10.0, -12.0, -12.0, -26.0], [40.0, 80.0, 100.0, 120.0, 120.0]),
+        ([350.0, 600.0, 2700.0, 2900.0, 3300.0], [0.0, -20.0, -17.0, -14.0, -26.0], [40.0, 60.0, 100.0, 120.0, 120.0]),
+    ],
+    // Bass
+    [
+        ([600.0, 1040.0, 2250.0, 2450.0, 2750.0], [0.0, -7.0, -9.0, -9.0, -20.0], [60.0, 70.0, 110.0, 120.0, 130.0]),
+        ([400.0, 1620.0, 2400.0, 2800.0, 3100.0], [0.0, -12.0, -9.0, -12.0, -18.0], [40.0, 80.0, 100.0, 120.0, 120.0]),
+        ([250.0, 1750.0, 2600.0, 3050.0, 3340.0], [0.0, -30.0, -16.0, -22.0, -28.0], [60.0, 90.0, 100.0, 120.0, 120.0]),
+        ([400.0, 750.0, 2400.0, 2600.0, 2900.0], [0.0, -11.0, -21.0, -20.0, -40.0], [40.0, 80.0, 100.0, 120.0, 120.0]),
+        ([350.0, 600.0, 2400.0, 2675.0, 2950.0], [0.0, -20.0, -32.0, -28.0, -36.0], [40.0, 80.0, 100.0, 120.0, 120.0]),
+    ],
+];
+
+// Each formant occupies `FORMANT_STRIDE` slots of the module's buffer: the
+// state used by `classic_biquad`.
+const FORMANT_STRIDE: usize = 13;
+
+/// A vowel filter, a parallel bank of 5 bandpass resonators tuned to the
+/// formants of a singing voice. `vowel` moves continuously through the vowels
+/// A (0.0), E (1.0), I (2.0), O (3.0) and U (4.0), interpolating the formant
+/// frequencies, amplitudes and bandwidths in between.
+#[derive(Debug, Copy, Clone)]
+pub struct Formant {
+    tag: Tag,
+    wave: Tag,
+}
+
+impl Formant {
+    pub fn new(tag: Tag, wave: Tag) -> Self {
+        Self { tag, wave }
+    }
+    props!(vowel, set_vowel, 0);
+    pub fn voice(&self, rack: &Rack) -> VoiceType {
+        let inp = rack.controls[(self.tag, 1)];
+        rack.outputs
+            .integer(inp)
+            .expect("voice must be Control::I")
+            .into()
+    }
+    pub fn set_voice(&self, rack: &mut Rack, value: VoiceType) {
+        rack.controls[(self.tag, 1)] = value.into();
+    }
+
+    /// The (frequency, linear amplitude, bandwidth) of each formant for the
+    /// current `vowel` and `voice`.
+    pub fn formants(&self, rack: &Rack) -> [(f32, f32, f32); NUM_FORMANTS] {
+        let table = &FORMANTS[self.voice(rack) as usize];
+        let v = self.vowel(rack).clamp(0.0, 4.0);
+        let i = (v.floor() as usize).min(3);
+        let x = v - i as f32;
+        let lerp = |a: f32, b: f32| a + x * (b - a);
+        let (v0, v1) = (&table[i], &table[i + 1]);
+        let mut formants = [(0.0, 0.0, 0.0); NUM_FORMANTS];
+        for (j, f) in formants.iter_mut().enumerate() {
+            *f = (
+                lerp(v0.0[j], v1.0[j]),
+                10f32.powf(lerp(v0.1[j], v1.1[j]) / 20.0),
+                lerp(v0.2[j], v1.2[j]),
+            );
+        }
+        formants
+    }
+}
+
+impl Signal for Formant {
+    tag!();
+    fn signal(&self, rack: &mut Rack, sample_rate: f32) {
+        let x0 = rack.outputs[(self.wave, 0)];
+        let formants = self.formants(rack);
+        let state = rack.buffers.buffers_mut(self.tag).as_mut_slice();
+        let mut out = 0.0;
+        for (i, (hz, amp, bw)) in formants.iter().enumerate() {
+            let q = hz / bw;
+            out += amp
+                * classic_biquad(
+                    &mut state[i * FORMANT_STRIDE..(i + 1) * FORMANT_STRIDE],
+                    x0,
+                    [*hz, q],
+                    |phi| bpf_coefficients(phi, q),
+                    sample_rate,
+                );
+        }
+        rack.outputs[(self.tag, 0)] = out;
+    }
+}
+
+#[derive(Debug, Copy, Clone)]
+pub struct FormantBuilder {
+    wave: Tag,
+    vowel: Control,
+    voice: Control,
+}
+
+impl FormantBuilder {
+    pub fn new(wave: Tag) -> Self {
+        Self {
+            wave,
+            vowel: 0.0.into(),
+            voice: VoiceType::Soprano.into(),
+        }
+    }
+
+    build!(vowel);
+    build!(voice);
+
+    pub fn rack(&self, rack: &mut Rack) -> Arc<Formant> {
+        let n = rack.num_modules();
+        rack.controls[(n, 0)] = self.vowel;
+        rack.controls[(n, 1)] = self.voice;
+        let formant = Arc::new(Formant::new(n.into(), self.wave));
+        rack.buffers.set_buffer(
+            formant.tag,
+            RingBuffer::new(0, vec![0.0; NUM_FORMANTS * FORMANT_STRIDE]),
+        );
+        rack.push(formant.clone());
+        formant
+    }
+}
+
+// Each vocoder band occupies `VOCODER_STRIDE` slots of the module's buffer:
+// the `classic_biquad` state of the two sections of the modulator's and then
+// the carrier's bandpass, followed by the modulator band's envelope.
+const VOCODER_STRIDE: usize = 4 * 13 + 1;
+pub const VOCODER_MAX_BANDS: usize = 32;
+
+/// A channel vocoder. The modulator, typically a voice, and the carrier, a
+/// harmonically rich synth, are split by matching banks of `bands` fourth
+/// order bandpass filters spaced logarithmically from `low` to `high` Hz. An
+/// envelope follower on each modulator band, rising in `attack` and falling in
+/// `release` seconds, sets the gain of the matching carrier band. Sibilants and
+/// other unvoiced sounds have little in the carrier to work with, so the
+/// modulator above `high` is highpassed and added to the output scaled by
+/// `unvoiced`.
+#[derive(Debug, Copy, Clone)]
+pub struct Vocoder {
+    tag: Tag,
+    modulator: Tag,
+    carrier: Tag,
+}
+
+impl Vocoder {
+    pub fn new(tag: Tag, modulator: Tag, carrier: Tag) -> Self {
+        Self {
+            tag,
+            modulator,
+            carrier,
+        }
+    }
+    props!(low, set_low, 1);
+    props!(high, set_high, 2);
+    props!(attack, set_attack, 3);
+    props!(release, set_release, 4);
+    props!(unvoiced, set_unvoiced, 5);
+    pub fn bands(&self, rack: &Rack) -> usize {
+        let inp = rack.controls[(self.tag, 0)];
+        rack.outputs
+            .integer(inp)
+            .expect("bands must be Control::I")
+            .clamp(1, VOCODER_MAX_BANDS)
+    }
+    pub fn set_bands(&self, rack: &mut Rack, value: usize) {
+        rack.controls[(self.tag, 0)] = value.into();
+    }
+
+    /// The lowest centre frequency, the ratio between neighbouring centres and
+    /// the q that makes neighbouring bands cross where each is 3 dB down.
+    fn spacing(&self, rack: &Rack) -> (f32, f32, f32) {
+        let bands = self.bands(rack);
+        let low = self.low(rack);
+        if bands == 1 {
+            return (low, 1.0, 1.0);
+        }
+        let r = (self.high(rack) / low).powf(1.0 / (bands - 1) as f32);
+        // Wider bands make the `bpf_coefficients` approximation unstable.
+        (low, r, (r.sqrt() / (r - 1.0)).max(1.0))
+    }
+
+    /// The centre frequency and q of each band.
+    pub fn band_frequencies(&self, rack: &Rack) -> Vec<(f32, f32)> {
+        let (low, r, q) = self.spacing(rack);
+        (0..self.bands(rack))
+            .map(|i| (low * r.powi(i as i32), q))
+            .collect()
+    }
+}
+
+impl Signal for Vocoder {
+    tag!();
+    fn signal(&self, rack: &mut Rack, sample_rate: f32) {
+        let m = rack.outputs[(self.modulator, 0)];
+        let c = rack.outputs[(self.carrier, 0)];
+        let attack = (-1.0 / (self.attack(rack).max(1e-5) * sample_rate)).exp();
+        let release = (-1.0 / (self.release(rack).max(1e-5) * sample_rate)).exp();
+        let bands = self.bands(rack);
+        let (low, r, q) = self.spacing(rack);
+        let state = rack.buffers.buffers_mut(self.tag).as_mut_slice();
+        let mut out = 0.0;
+        for i in 0..bands {
+            let hz = low * r.powi(i as i32);
+            let band = &mut state[i * VOCODER_STRIDE..(i + 1) * VOCODER_STRIDE];
+            let mut bpf = |section: usize, x: f32| {
+                let state = &mut band[section * 13..(section + 1) * 13];
+                classic_biquad(
+                    state,
+                    x,
+                    [hz, q],
+                    |phi| bpf_coefficients(phi, q),
+                    sample_rate,
+                )
+            };
+            // Two sections per band for steeper skirts, so a loud carrier
+            // partial in one band does not leak into its neighbours.
+            let mb = bpf(0, m);
+            let mb = bpf(1, mb);
+            let cb = bpf(2, c);
+            let cb = bpf(3, cb);
+            let env = &mut band[4 * 13];
+            let a = if mb.abs() > *env { attack } else { release };
+            *env = a * *env + (1.0 - a) * mb.abs();
+            out += *env * cb;
+        }
+        let high = self.high(rack);
+        let q = 0.707;
+        let hiss = classic_biquad(
+            rack.state.state_mut(self.tag),
+            m,
+            [high, q],
+            |phi| hpf_coefficients(phi, q),
+            sample_rate,
+        );
+        rack.outputs[(self.tag, 0)] = out + self.unvoiced(rack) * hiss;
+    }
+}
+
+#[derive(Debug, Copy, Clone)]
+pub struct VocoderBuilder {
+    modulator: Tag,
+    carrier: Tag,
+    bands: Control,
+    low: Control,
+    high: Control,
+    attack: Control,
+    release: Control,
+    unvoiced: Control,
+}
+
+impl VocoderBuilder {
+    pub fn new(modulator: Tag, carrier: Tag) -> Self {
+        Self {
+            modulator,
+            carrier,
+            bands: 16.into(),
+            low: 100.0.into(),
+            high: 8_000.0.into(),
+            attack: 0.005.into(),
+            release: 0.05.into(),
+            unvoiced: 0.5.into(),
+        }
+    }
+
+    build!(bands);
+    build!(low);
+    build!(high);
+    build!(attack);
+    build!(release);
+    build!(unvoiced);
+
+    pub fn rack(&self, rack: &mut Rack) -> Arc<Vocoder> {
+        let n = rack.num_modules();
+        rack.controls[(n, 0)] = self.bands;
+        rack.controls[(n, 1)] = self.low;
+        rack.controls[(n, 2)] = self.high;
+        rack.controls[(n, 3)] = self.attack;
+        rack.controls[(n, 4)] = self.release;
+        rack.controls[(n, 5)] = self.unvoiced;
+        let vocoder = Arc::new(Vocoder::new(n.into(), self.modulator, self.carrier));
+        rack.buffers.set_buffer(
+            vocoder.tag,
+            RingBuffer::new(0, vec![0.0; VOCODER_MAX_BANDS * VOCODER_STRIDE]),
+        );
+        rack.push(vocoder.clone());
+        vocoder
+    }
+}
+
+/// First order zero-delay-feedback filter, the lowpass output is written to
+/// `outputs[0]` and the highpass to `outputs[1]`. Both roll off at 6 dB/oct.
+#[derive(Debug, Copy, Clone)]
+pub struct OnePole {
+    tag: Tag,
+    wave: Tag,
+}
+
+impl OnePole {
+    pub const LOWPASS: usize = 0;
+    pub const HIGHPASS: usize = 1;
+
+    pub fn new(tag: Tag, wave: Tag) -> Self {
+        Self { tag, wave }
+    }
+    props!(cutoff, set_cutoff, 0);
+}
+
+impl Signal for OnePole {
+    tag!();
+    fn signal(&self, rack: &mut Rack, sample_rate: f32) {
+        let tag = self.tag;
+        let x = rack.outputs[(self.wave, 0)];
+        let cut_off = self.cutoff(rack);
+        // state: 0 - the integrator, 1 - 4 the cached gain.
+        let [big_g] = cached(
+            &mut rack.state.state_mut(tag)[1..],
+            [cut_off, sample_rate],
+            || {
+                let g = (PI * cut_off.clamp(1.0, 0.49 * sample_rate) / sample_rate).tan();
+                [g / (1.0 + g)]
+            },
+        );
+        let s = rack.state[(tag, 0)];
+        let v = (x - s) * big_g;
+        let low = v + s;
+        rack.state[(tag, 0)] = low + v;
+        rack.outputs[(tag, Self::LOWPASS)] = low;
+        rack.outputs[(tag, Self::HIGHPASS)] = x - low;
+    }
+}
+
+#[derive(Debug, Copy, Clone)]
+pub struct OnePoleBuilder {
+    wave: Tag,
+    cut_off: Control,
+}
+
+impl OnePoleBuilder {
+    pub fn new(wave: Tag) -> Self {
+        Self {
+            wave,
+            cut_off: 1_000.0.into(),
+        }
+    }
+
+    build!(cut_off);
+
+    pub fn rack(&self, rack: &mut Rack) -> Arc<OnePole> {
+        let n = rack.num_modules();
+        rack.controls[(n, 0)] = self.cut_off;
+        let one_pole = Arc::new(OnePole::new(n.into(), self.wave));
+        rack.push(one_pole.clone());
+        one_pole
+    }
+}
+
+/// Removes any constant offset from a signal, e.g. after asymmetric wave
+/// shaping or in feedback loops. A one pole, one zero highpass at `cutoff` Hz.
+#[derive(Debug, Copy, Clone)]
+pub struct DcBlocker {
+    tag: Tag,
+    wave: Tag,
+}
+
+impl DcBlocker {
+    pub fn new(tag: Tag, wave: Tag) -> Self {
+        Self { tag, wave }
+    }
+    props!(cutoff, set_cutoff, 0);
+}
+
+impl Signal for DcBlocker {
+    tag!();
+    fn signal(&self, rack: &mut Rack, sample_rate: f32) {
+        let tag = self.tag;
+        let x = rack.outputs[(self.wave, 0)];
+        let cut_off = self.cutoff(rack);
+        // state: 0 - previous input, 1 - previous output, 2 - 5 the cached
+        // pole.
+        let [r] = cached(
+            &mut rack.state.state_mut(tag)[2..],
+            [cut_off, sample_rate],
+            || [(-2.0 * PI * cut_off / sample_rate).exp()],
+        );
+        let y = x - rack.state[(tag, 0)] + r * rack.state[(tag, 1)];
+        rack.state[(tag, 0)] = x;
+        rack.state[(tag, 1)] = if y.is_finite() { y } else { 0.0 };
+        rack.outputs[(tag, 0)] = y;
+    }
+}
+
+#[derive(Debug, Copy, Clone)]
+pub struct DcBlockerBuilder {
+    wave: Tag,
+    cut_off: Control,
+}
+
+impl DcBlockerBuilder {
+    pub fn new(wave: Tag) -> Self {
+        Self {
+            wave,
+            cut_off: 10.0.into(),
+        }
+    }
+
+    build!(cut_off);
+
+    pub fn rack(&self, rack: &mut Rack) -> Arc<DcBlocker> {
+        let n = rack.num_modules();
+        rack.controls[(n, 0)] = self.cut_off;
+        let dc = Arc::new(DcBlocker::new(n.into(), self.wave));
+        rack.push(dc.clone());
+        dc
+    }
+}
+
+/// Smooths changes in a signal, e.g. for portamento or to de-zipper control
+/// signals. `rise` and `fall` are the times in seconds for the output to get
+/// within 1% of a higher or lower input.
+#[derive(Debug, Copy, Clone)]
+pub struct Slew {
+    tag: Tag,
+    wave: Tag,
+}
+
+impl Slew {
+    pub fn new(tag: Tag, wave: Tag) -> Self {
+        Self { tag, wave }
+    }
+    props!(rise, set_rise, 0);
+    props!(fall, set_fall, 1);
+}
+
+impl Signal for Slew {
+    tag!();
+    fn signal(&self, rack: &mut Rack, sample_rate: f32) {
+        let tag = self.tag;
+        let x = rack.outputs[(self.wave, 0)];
+        let y = rack.state[(tag, 0)];
+        let time = if x > y {
+            self.rise(rack)
+        } else {
+            self.fall(rack)
+        };
+        let y = if time > 0.0 {
+            let a = (-(100f32.ln()) / (time * sample_rate)).exp();
+            x + a * (y - x)
+        } else {
+            x
+        };
+        rack.state[(tag, 0)] = y;
+        rack.outputs[(tag, 0)] = y;
+    }
+}
+
+#[derive(Debug, Copy, Clone)]
+pub struct SlewBuilder {
+    wave: Tag,
+    rise: Control,
+    fall: Control,
+}
+
+impl SlewBuilder {
+    pub fn new(wave: Tag) -> Self {
+        Self {
+            wave,
+            rise: 0.05.into(),
+            fall: 0.05.into(),
+        }
+    }
+
+    build!(rise);
+    build!(fall);
+
+    pub fn rack(&self, rack: &mut Rack) -> Arc<Slew> {
+        let n = rack.num_modules();
+        rack.controls[(n, 0)] = self.rise;
+        rack.controls[(n, 1)] = self.fall;
+        let slew = Arc::new(Slew::new(n.into(), self.wave));
+        rack.push(slew.clone());
+        slew
+    }
+}
+
 /// Lowpass-Feedback Comb Filter
-// https://ccrma.stanford.edu/~jos/pasp/Lowpass_Feedback_Comb_Filter.html
+/// <https://ccrma.stanford.edu/~jos/pasp/Lowpass_Feedback_Comb_Filter.html>
+///
+/// The delay is a `Control` in seconds, or in Hz when built with
+/// [`CombBuilder::hz`] so the comb can be tuned to a pitch. It is read with
+/// cubic interpolation so it can be modulated smoothly. In the default feedback
+/// topology the output is the delayed signal. With `feedforward` the output is
+/// the input plus the (damped) delayed input scaled by `feedback`. Delays
+/// longer than the delay line are cut short.
 #[derive(Clone)]
 pub struct Comb {
     tag: Tag,
     wave: Tag,
+    hz: bool,
 }
 
 impl Comb {
-    pub fn new<T: Into<Tag>>(tag: T, wave: Tag) -> Self {
+    pub fn new<T: Into<Tag>>(tag: T, wave: Tag, hz: bool) -> Self {
         Self {
             tag: tag.into(),
             wave,
+            hz,
         }
     }
     props!(feedback, set_feedback, 0);
     props!(dampening, set_dampening, 1);
     props!(dampening_inverse, set_dampening_inverse, 2);
+    props!(delay, set_delay, 3);
+    pub fn feedforward(&self, rack: &Rack) -> bool {
+        let ctrl = rack.controls[(self.tag, 4)];
+        match ctrl {
+            Control::B(b) => b,
+            _ => panic!("feedforward must be a bool, not {ctrl:?}"),
+        }
+    }
+    pub fn set_feedforward(&self, rack: &mut Rack, value: bool) {
+        rack.controls[(self.tag, 4)] = value.into();
+    }
+
+    /// The delay in samples.
+    fn delay_samples(&self, rack: &Rack, sample_rate: f32) -> f32 {
+        let d = self.delay(rack);
+        let seconds = if self.hz { 1.0 / d } else { d };
+        let max = rack.buffers.buffers(self.tag).len() as f32 - 3.0;
+        (seconds * sample_rate).clamp(3.0, max)
+    }
 }
 
 impl Signal for Comb {
     tag!();
-    fn signal(&self, rack: &mut Rack, _sample_rate: f32) {
-        rack.outputs[(self.tag, 0)] = rack.buffers.buffers(self.tag).get_max_delay();
-        rack.state[(self.tag, 0)] = rack.outputs[(self.tag, 0)] * self.dampening_inverse(rack)
-            + rack.state[(self.tag, 0)] * self.dampening(rack);
+    fn signal(&self, rack: &mut Rack, sample_rate: f32) {
+        let tag = self.tag;
+        let input = rack.outputs[(self.wave, 0)];
+        let d = self.delay_samples(rack, sample_rate);
+        let delayed = rack.buffers.buffers(tag).get_cubic(d - 1.0);
+        rack.state[(tag, 0)] =
+            delayed * self.dampening_inverse(rack) + rack.state[(tag, 0)] * self.dampening(rack);
         let feedback = self.feedback(rack);
-        rack.buffers
-            .buffers_mut(self.tag)
-            .push(rack.outputs[(self.wave, 0)] + rack.state[(self.tag, 0)] * feedback);
+        if self.feedforward(rack) {
+            rack.outputs[(tag, 0)] = input + rack.state[(tag, 0)] * feedback;
+            rack.buffers.buffers_mut(tag).push(input);
+        } else {
+            rack.outputs[(tag, 0)] = delayed;
+            rack.buffers
+                .buffers_mut(tag)
+                .push(input + rack.state[(tag, 0)] * feedback);
+        }
     }
 }
 
 #[derive(Clone)]
 pub struct CombBuilder {
     wave: Tag,
-    length: usize,
+    hz: bool,
+    max_delay: f32,
+    max_sample_rate: f32,
     feedback: Control,
     dampening: Control,
     dampening_inverse: Control,
+    delay: Control,
+    feedforward: Control,
 }
 
 impl CombBuilder {
-    pub fn new(wave: Tag, length: usize) -> Self {
+    pub fn new(wave: Tag) -> Self {
         Self {
             wave,
-            length,
+            hz: false,
+            max_delay: 0.1,
+            max_sample_rate: MAX_SAMPLE_RATE,
             feedback: 0.5.into(),
             dampening: 0.5.into(),
             dampening_inverse: 0.5.into(),
+            delay: 0.01.into(),
+            feedforward: false.into(),
         }
     }
 
     build!(feedback);
     build!(dampening);
     build!(dampening_inverse);
+    build!(feedforward);
+
+    /// The delay in seconds.
+    pub fn delay<T: Into<Control>>(&mut self, value: T) -> &mut Self {
+        self.delay = value.into();
+        self.hz = false;
+        self
+    }
+
+    /// Tune the comb to `value` Hz, i.e. a delay of `1 / value` seconds.
+    pub fn hz<T: Into<Control>>(&mut self, value: T) -> &mut Self {
+        self.delay = value.into();
+        self.hz = true;
+        self
+    }
+
+    /// The longest delay in seconds, defaults to 0.1.
+    pub fn max_delay(&mut self, value: f32) -> &mut Self {
+        self.max_delay = value;
+        self
+    }
+
+    /// The highest sample rate the delay line is sized for, defaults to
+    /// [`MAX_SAMPLE_RATE`].
+    pub fn max_sample_rate(&mut self, value: f32) -> &mut Self {
+        self.max_sample_rate = value;
+        self
+    }
 
     pub fn rack(&mut self, rack: &mut Rack) -> Arc<Comb> {
         let n = rack.num_modules();
         rack.controls[(n, 0)] = self.feedback;
         rack.controls[(n, 1)] = self.dampening;
         rack.controls[(n, 2)] = self.dampening_inverse;
-        let comb = Arc::new(Comb::new(n, self.wave));
+        rack.controls[(n, 3)] = self.delay;
+        rack.controls[(n, 4)] = self.feedforward;
+        let comb = Arc::new(Comb::new(n, self.wave, self.hz));
+        let len = (self.max_delay * self.max_sample_rate) as usize + 4;
         rack.buffers
-            .set_buffer(comb.tag, RingBuffer::new(1, vec![0.0; self.length]));
+            .set_buffer(comb.tag, RingBuffer::new(0, vec![0.0; len]));
         rack.push(comb.clone());
         comb
     }
@@ -25,6 +25,8 @@
 //! [`Signal`]: signal/trait.Signal.html
 //! [`Rack`]: signal/struct.Rack.html
 
+/// Delay, modulation and dynamics effects.
+pub mod effects;
 /// Envelope generators.
 pub mod envelopes;
 /// A collection of some basic audio filters.
@@ -38,7 +40,7 @@ pub mod oscillators;
 /// Core Oscen types and traits.
 pub mod rack;
 /// An implementation of *freeverb*.
-// pub mod reverb;
+pub mod reverb;
 /// Wave shaping.
 pub mod shaping;
 /// Utilites.
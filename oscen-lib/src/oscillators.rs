@@ -1,6 +1,7 @@
 use crate::rack::*;
 use crate::{build, props, tag};
 use math::round::floor;
+use parking_lot::Mutex;
 use rand::prelude::*;
 use rand_distr::{StandardNormal, Uniform};
 use std::f32::consts;
@@ -122,6 +123,177 @@ impl Signal for Oscillator {
     }
 }
 
+pub struct UnisonOscBuilder {
+    signal_fn: fn(f32, f32) -> f32,
+    voices: usize,
+    random_phase: bool,
+    hz: Control,
+    amplitude: Control,
+    arg: Control,
+    detune: Control,
+    curve: Control,
+    spread: Control,
+    mix: Control,
+}
+
+/// A stack of detuned copies of a signal function, e.g. a *supersaw*. `detune`
+/// is the distance in cents between the outermost voices and the centre,
+/// `curve` shapes how the voices in between are distributed (1.0 is even,
+/// larger values bunch them near the centre). `spread` pans the voices across
+/// the stereo field and `mix` crossfades between the centre voice(s) and the
+/// side voices. The left channel is written to `outputs[0]` and the right to
+/// `outputs[1]`.
+#[derive(Clone)]
+pub struct UnisonOsc {
+    tag: Tag,
+    signal_fn: fn(f32, f32) -> f32,
+    voices: usize,
+}
+
+impl UnisonOscBuilder {
+    pub fn new(signal_fn: fn(f32, f32) -> f32, voices: usize) -> Self {
+        Self {
+            signal_fn,
+            voices,
+            random_phase: true,
+            hz: 0.0.into(),
+            amplitude: 1.0.into(),
+            arg: 0.5.into(),
+            detune: 25.0.into(),
+            curve: 1.0.into(),
+            spread: 1.0.into(),
+            mix: 0.5.into(),
+        }
+    }
+
+    /// Start each voice at a random phase, defaults to `true`. Otherwise all
+    /// voices start at phase 0.
+    pub fn random_phase(&mut self, value: bool) -> &mut Self {
+        self.random_phase = value;
+        self
+    }
+
+    build!(hz);
+    build!(amplitude);
+    build!(arg);
+    build!(detune);
+    build!(curve);
+    build!(spread);
+    build!(mix);
+
+    pub fn rack(&self, rack: &mut Rack) -> Arc<UnisonOsc> {
+        let n = rack.num_modules();
+        rack.controls[(n, 0)] = self.hz;
+        rack.controls[(n, 1)] = self.amplitude;
+        rack.controls[(n, 2)] = self.arg;
+        rack.controls[(n, 3)] = self.detune;
+        rack.controls[(n, 4)] = self.curve;
+        rack.controls[(n, 5)] = self.spread;
+        rack.controls[(n, 6)] = self.mix;
+        let osc = Arc::new(UnisonOsc::new(n, self.signal_fn, self.voices));
+        let mut rng = thread_rng();
+        for v in 0..self.voices {
+            rack.state[(n, v)] = if self.random_phase { rng.gen() } else { 0.0 };
+        }
+        rack.push(osc.clone());
+        osc
+    }
+}
+
+impl UnisonOsc {
+    pub fn new<T: Into<Tag>>(tag: T, signal_fn: fn(f32, f32) -> f32, voices: usize) -> Self {
+        assert!(
+            voices > 0 && voices <= MAX_STATE,
+            "Number of unison voices must be between 1 and {MAX_STATE}"
+        );
+        Self {
+            tag: tag.into(),
+            signal_fn,
+            voices,
+        }
+    }
+    pub fn voices(&self) -> usize {
+        self.voices
+    }
+    props!(hz, set_hz, 0);
+    props!(amplitude, set_amplitude, 1);
+    props!(arg, set_arg, 2);
+    props!(detune, set_detune, 3);
+    props!(curve, set_curve, 4);
+    props!(spread, set_spread, 5);
+    props!(mix, set_mix, 6);
+
+    /// The position of voice `v` in [-1, 1], 0 being the centre.
+    fn position(&self, v: usize) -> f32 {
+        if self.voices == 1 {
+            0.0
+        } else {
+            2.0 * v as f32 / (self.voices - 1) as f32 - 1.0
+        }
+    }
+
+    fn is_centre(&self, v: usize) -> bool {
+        let mid = (self.voices - 1) as f32 / 2.0;
+        (v as f32 - mid).abs() <= 0.5
+    }
+}
+
+impl Signal for UnisonOsc {
+    tag!();
+    fn signal(&self, rack: &mut Rack, sample_rate: f32) {
+        let hz = self.hz(rack);
+        let amp = self.amplitude(rack);
+        let arg = self.arg(rack);
+        let detune = self.detune(rack);
+        let curve = self.curve(rack);
+        let spread = self.spread(rack).clamp(0.0, 1.0);
+        let mix = self.mix(rack).clamp(0.0, 1.0);
+        let num_centre = (0..self.voices).filter(|v| self.is_centre(*v)).count();
+        let num_sides = self.voices - num_centre;
+        let centre_gain = if num_sides == 0 {
+            1.0
+        } else {
+            (1.0 - mix) / (num_centre as f32).sqrt()
+        };
+        let side_gain = if num_sides == 0 {
+            0.0
+        } else {
+            mix / (num_sides as f32).sqrt()
+        };
+        let (mut left, mut right) = (0.0, 0.0);
+        for v in 0..self.voices {
+            let x = self.position(v);
+            // The centre voice is never detuned, even if `curve` is 0.
+            let cents = if x == 0.0 {
+                0.0
+            } else {
+                detune * x.signum() * x.abs().powf(curve)
+            };
+            let voice_hz = hz * 2f32.powf(cents / 1200.0);
+            let phase = rack.state[(self.tag, v)];
+            let gain = if self.is_centre(v) {
+                centre_gain
+            } else {
+                side_gain
+            };
+            let out = gain * (self.signal_fn)(phase, arg);
+            let pan = (spread * x + 1.0) * consts::FRAC_PI_4;
+            left += pan.cos() * out;
+            right += pan.sin() * out;
+            let mut ph = phase + voice_hz / sample_rate;
+            while ph >= 1.0 {
+                ph -= 1.0
+            }
+            while ph <= -1.0 {
+                ph += 1.0
+            }
+            rack.state[(self.tag, v)] = ph;
+        }
+        rack.outputs[(self.tag, 0)] = amp * consts::SQRT_2 * left;
+        rack.outputs[(self.tag, 1)] = amp * consts::SQRT_2 * right;
+    }
+}
+
 #[derive(Debug, Copy, Clone)]
 pub struct ConstBuilder {
     value: Control,
@@ -167,17 +339,61 @@ pub enum NoiseDistribution {
     Uni,
 }
 
+/// The source of randomness for the noise modules. Seeded generators produce
+/// the same sequence every time, otherwise `thread_rng` is used.
+pub struct NoiseRng {
+    seeded: Option<Mutex<StdRng>>,
+}
+
+impl NoiseRng {
+    pub fn new(seed: Option<u64>) -> Self {
+        Self {
+            seeded: seed.map(|s| Mutex::new(StdRng::seed_from_u64(s))),
+        }
+    }
+
+    pub fn sample<D: Distribution<f32>>(&self, dist: D) -> f32 {
+        match &self.seeded {
+            Some(rng) => dist.sample(&mut *rng.lock()),
+            None => dist.sample(&mut thread_rng()),
+        }
+    }
+
+    /// A uniformly distributed sample in [-1, 1].
+    pub fn white(&self) -> f32 {
+        self.sample(Uniform::new_inclusive(-1.0, 1.0))
+    }
+
+    /// A uniformly distributed sample in [0, 1).
+    pub fn unit(&self) -> f32 {
+        self.sample(Uniform::new(0.0, 1.0))
+    }
+}
+
+impl Clone for NoiseRng {
+    fn clone(&self) -> Self {
+        Self {
+            seeded: self
+                .seeded
+                .as_ref()
+                .map(|rng| Mutex::new(rng.lock().clone())),
+        }
+    }
+}
+
 /// White noise oscillator.
-#[derive(Copy, Clone)]
+#[derive(Clone)]
 pub struct WhiteNoise {
     tag: Tag,
     dist: NoiseDistribution,
+    rng: NoiseRng,
 }
 
 #[derive(Copy, Clone)]
 pub struct WhiteNoiseBuilder {
     amplitude: Control,
     dist: NoiseDistribution,
+    seed: Option<u64>,
 }
 
 impl Default for WhiteNoiseBuilder {
@@ -185,6 +401,7 @@ impl Default for WhiteNoiseBuilder {
         Self {
             amplitude: 1.0.into(),
             dist: NoiseDistribution::StdNormal,
+            seed: None,
         }
     }
 }
@@ -197,21 +414,26 @@ impl WhiteNoiseBuilder {
         self.dist = arg;
         self
     }
+    pub fn seed(&mut self, value: u64) -> &mut Self {
+        self.seed = Some(value);
+        self
+    }
     build!(amplitude);
     pub fn rack(&self, rack: &mut Rack) -> Arc<WhiteNoise> {
         let n = rack.num_modules();
         rack.controls[(n, 0)] = self.amplitude;
-        let noise = Arc::new(WhiteNoise::new(n, self.dist));
+        let noise = Arc::new(WhiteNoise::new(n, self.dist, self.seed));
         rack.push(noise.clone());
         noise
     }
 }
 
 impl WhiteNoise {
-    pub fn new<T: Into<Tag>>(tag: T, dist: NoiseDistribution) -> Self {
+    pub fn new<T: Into<Tag>>(tag: T, dist: NoiseDistribution, seed: Option<u64>) -> Self {
         Self {
             tag: tag.into(),
             dist,
+            rng: NoiseRng::new(seed),
         }
     }
     props!(amplitude, set_amplitude, 0);
@@ -221,38 +443,54 @@ impl Signal for WhiteNoise {
     tag!();
     fn signal(&self, rack: &mut Rack, _sample_rate: f32) {
         let amplitude = self.amplitude(rack);
-        let mut rng = thread_rng();
         let out = match self.dist {
-            NoiseDistribution::Uni => {
-                amplitude * Uniform::new_inclusive(-1.0, 1.0).sample(&mut rng)
-            }
-            NoiseDistribution::StdNormal => amplitude * rng.sample::<f32, _>(StandardNormal),
+            NoiseDistribution::Uni => amplitude * self.rng.white(),
+            NoiseDistribution::StdNormal => amplitude * self.rng.sample(StandardNormal),
         };
         rack.outputs[(self.tag, 0)] = out;
     }
 }
 
-#[derive(Copy, Clone)]
+// Paul Kellet's refined pink noise filter, uses the first 7 slots of `state`.
+fn pink_filter(state: &mut [f32], white: f32) -> f32 {
+    state[0] = 0.99886 * state[0] + white * 0.0555179;
+    state[1] = 0.99332 * state[1] + white * 0.0750759;
+    state[2] = 0.969 * state[2] + white * 0.153852;
+    state[3] = 0.8665 * state[3] + white * 0.3104856;
+    state[4] = 0.55 * state[4] + white * 0.5329522;
+    state[5] = -0.7616 * state[5] - white * 0.016898;
+    let pink = state[0..7].iter().sum::<f32>() + white * 0.5362;
+    state[6] = white * 0.115926;
+    pink
+}
+
+#[derive(Clone)]
 pub struct PinkNoise {
     tag: Tag,
+    rng: NoiseRng,
 }
 
 #[derive(Copy, Clone)]
 pub struct PinkNoiseBuilder {
     amplitude: Control,
+    seed: Option<u64>,
 }
 
 impl Default for PinkNoiseBuilder {
     fn default() -> Self {
         Self {
             amplitude: 1.0.into(),
+            seed: None,
         }
     }
 }
 
 impl PinkNoise {
-    pub fn new<T: Into<Tag>>(tag: T) -> Self {
-        Self { tag: tag.into() }
+    pub fn new<T: Into<Tag>>(tag: T, seed: Option<u64>) -> Self {
+        Self {
+            tag: tag.into(),
+            rng: NoiseRng::new(seed),
+        }
     }
     props!(amplitude, set_amplitude, 0);
 }
@@ -261,11 +499,15 @@ impl PinkNoiseBuilder {
     pub fn new() -> Self {
         Self::default()
     }
+    pub fn seed(&mut self, value: u64) -> &mut Self {
+        self.seed = Some(value);
+        self
+    }
     build!(amplitude);
     pub fn rack(&self, rack: &mut Rack) -> Arc<PinkNoise> {
         let n = rack.num_modules();
         rack.controls[(n, 0)] = self.amplitude;
-        let noise = Arc::new(PinkNoise::new(n));
+        let noise = Arc::new(PinkNoise::new(n, self.seed));
         rack.push(noise.clone());
         noise
     }
@@ -274,33 +516,255 @@ impl PinkNoiseBuilder {
 impl Signal for PinkNoise {
     tag!();
     fn signal(&self, rack: &mut Rack, _sample_rate: f32) {
-        let tag = self.tag;
         let amplitude = self.amplitude(rack);
-        let mut rng = thread_rng();
-        let white = Uniform::new_inclusive(-1.0, 1.0).sample(&mut rng);
-        rack.state[(tag, 0)] = 0.99886 * rack.state[(tag, 0)] + white * 0.0555179;
-        rack.state[(tag, 1)] = 0.99332 * rack.state[(tag, 1)] + white * 0.0750759;
-        rack.state[(tag, 2)] = 0.969 * rack.state[(tag, 2)] + white * 0.153852;
-        rack.state[(tag, 3)] = 0.8665 * rack.state[(tag, 3)] + white * 0.3104856;
-        rack.state[(tag, 4)] = 0.55 * rack.state[(tag, 4)] + white * 0.5329522;
-        rack.state[(tag, 5)] = -0.7616 * rack.state[(tag, 5)] - white * 0.016898;
-        let pink = rack.state[(tag, 0)]
-            + rack.state[(tag, 1)]
-            + rack.state[(tag, 2)]
-            + rack.state[(tag, 3)]
-            + rack.state[(tag, 4)]
-            + rack.state[(tag, 5)]
-            + rack.state[(tag, 6)]
-            + white * 0.5362;
-        rack.state[(tag, 6)] = white * 0.115926;
+        let white = self.rng.white();
+        let pink = pink_filter(rack.state.state_mut(self.tag), white);
         rack.outputs[(self.tag, 0)] = pink * amplitude;
     }
 }
 
+/// The spectra of a [`ColoredNoise`].
+///
+/// * `Brown` - white noise through a leaky integrator, falls off at 6 dB per
+///   octave above 20 Hz.
+/// * `Blue` - the first difference of pink noise, rises at 3 dB per octave.
+/// * `Violet` - the first difference of white noise, rises at 6 dB per octave.
+/// * `Velvet` - one impulse of random sign at a random position in each period
+///   of `1 / density` seconds and silence otherwise.
+/// * `Dust` - impulses of random height at random times, on average `density`
+///   per second, i.e. `density` is the dust rate. Sparse rates sound like vinyl
+///   crackle. Impulses are in [0, 1]
+///   unless `bipolar` in which case they are in [-1, 1].
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum NoiseColor {
+    Brown,
+    Blue,
+    Violet,
+    Velvet,
+    Dust,
+}
+
+impl From<NoiseColor> for Control {
+    fn from(color: NoiseColor) -> Self {
+        Control::I(color as usize)
+    }
+}
+
+impl From<usize> for NoiseColor {
+    fn from(u: usize) -> Self {
+        match u {
+            0 => NoiseColor::Brown,
+            1 => NoiseColor::Blue,
+            2 => NoiseColor::Violet,
+            3 => NoiseColor::Velvet,
+            4 => NoiseColor::Dust,
+            _ => panic!("No NoiseColor with index {u}"),
+        }
+    }
+}
+
+/// Below this frequency brown noise is flat.
+const BROWN_CORNER: f32 = 20.0;
+/// The rms level of brown noise.
+const BROWN_RMS: f32 = 0.2;
+
+/// Noise with a non white spectrum, see [`NoiseColor`] for the colors.
+#[derive(Clone)]
+pub struct ColoredNoise {
+    tag: Tag,
+    rng: NoiseRng,
+}
+
+#[derive(Copy, Clone)]
+pub struct ColoredNoiseBuilder {
+    amplitude: Control,
+    color: Control,
+    density: Control,
+    bipolar: Control,
+    seed: Option<u64>,
+}
+
+impl ColoredNoiseBuilder {
+    pub fn new(color: NoiseColor) -> Self {
+        let density = if color == NoiseColor::Dust {
+            10.0
+        } else {
+            2000.0
+        };
+        Self {
+            amplitude: 1.0.into(),
+            color: color.into(),
+            density: density.into(),
+            bipolar: false.into(),
+            seed: None,
+        }
+    }
+    pub fn seed(&mut self, value: u64) -> &mut Self {
+        self.seed = Some(value);
+        self
+    }
+    build!(amplitude);
+    build!(color);
+    build!(density);
+    build!(bipolar);
+    pub fn rack(&self, rack: &mut Rack) -> Arc<ColoredNoise> {
+        let n = rack.num_modules();
+        rack.controls[(n, 0)] = self.amplitude;
+        rack.controls[(n, 1)] = self.color;
+        rack.controls[(n, 2)] = self.density;
+        rack.controls[(n, 3)] = self.bipolar;
+        let noise = Arc::new(ColoredNoise::new(n, self.seed));
+        rack.push(noise.clone());
+        noise
+    }
+}
+
+impl ColoredNoise {
+    pub fn new<T: Into<Tag>>(tag: T, seed: Option<u64>) -> Self {
+        Self {
+            tag: tag.into(),
+            rng: NoiseRng::new(seed),
+        }
+    }
+    props!(amplitude, set_amplitude, 0);
+    props!(density, set_density, 2);
+
+    pub fn color(&self, rack: &Rack) -> NoiseColor {
+        let inp = rack.controls[(self.tag, 1)];
+        rack.outputs
+            .integer(inp)
+            .expect("color must be Control::I")
+            .into()
+    }
+    /// Switch color. The colors share their filter state, so it is cleared to
+    /// start the new color without a transient.
+    pub fn set_color(&self, rack: &mut Rack, value: NoiseColor) {
+        rack.controls[(self.tag, 1)] = value.into();
+        rack.state.state_mut(self.tag).fill(0.0);
+    }
+    pub fn bipolar(&self, rack: &Rack) -> bool {
+        let inp = rack.controls[(self.tag, 3)];
+        rack.outputs.boolean(inp).unwrap()
+    }
+    pub fn set_bipolar(&self, rack: &mut Rack, value: bool) {
+        rack.controls[(self.tag, 3)] = value.into();
+    }
+
+    fn brown(&self, rack: &mut Rack, sample_rate: f32) -> f32 {
+        let tag = self.tag;
+        let a = (-TAU * BROWN_CORNER / sample_rate).exp();
+        rack.state[(tag, 0)] = a * rack.state[(tag, 0)] + (1.0 - a) * self.rng.white();
+        // The white noise has variance 1/3, the integrator scales it by
+        // (1 - a) / (1 + a).
+        BROWN_RMS * (3.0 * (1.0 + a) / (1.0 - a)).sqrt() * rack.state[(tag, 0)]
+    }
+
+    fn blue(&self, rack: &mut Rack) -> f32 {
+        let tag = self.tag;
+        let pink = pink_filter(rack.state.state_mut(tag), self.rng.white());
+        let blue = pink - rack.state[(tag, 7)];
+        rack.state[(tag, 7)] = pink;
+        blue
+    }
+
+    fn violet(&self, rack: &mut Rack) -> f32 {
+        let tag = self.tag;
+        let white = self.rng.white();
+        let violet = 0.5 * (white - rack.state[(tag, 0)]);
+        rack.state[(tag, 0)] = white;
+        violet
+    }
+
+    fn velvet(&self, rack: &mut Rack, sample_rate: f32) -> f32 {
+        let tag = self.tag;
+        let period = (sample_rate / self.density(rack)).round().max(1.0);
+        // state: 0 - samples into the current period, 1 - impulse position,
+        // 2 - impulse sign.
+        if rack.state[(tag, 0)] == 0.0 {
+            rack.state[(tag, 1)] = (self.rng.unit() * period).floor();
+            rack.state[(tag, 2)] = if self.rng.unit() < 0.5 { -1.0 } else { 1.0 };
+        }
+        let out = if rack.state[(tag, 0)] == rack.state[(tag, 1)] {
+            rack.state[(tag, 2)]
+        } else {
+            0.0
+        };
+        rack.state[(tag, 0)] += 1.0;
+        if rack.state[(tag, 0)] >= period {
+            rack.state[(tag, 0)] = 0.0;
+        }
+        out
+    }
+
+    fn dust(&self, rack: &Rack, sample_rate: f32) -> f32 {
+        let p = (self.density(rack) / sample_rate).clamp(0.0, 1.0);
+        let r = self.rng.unit();
+        if r < p {
+            let height = r / p;
+            if self.bipolar(rack) {
+                2.0 * height - 1.0
+            } else {
+                height
+            }
+        } else {
+            0.0
+        }
+    }
+}
+
+impl Signal for ColoredNoise {
+    tag!();
+    fn signal(&self, rack: &mut Rack, sample_rate: f32) {
+        let amplitude = self.amplitude(rack);
+        let out = match self.color(rack) {
+            NoiseColor::Brown => self.brown(rack, sample_rate),
+            NoiseColor::Blue => self.blue(rack),
+            NoiseColor::Violet => self.violet(rack),
+            NoiseColor::Velvet => self.velvet(rack, sample_rate),
+            NoiseColor::Dust => self.dust(rack, sample_rate),
+        };
+        rack.outputs[(self.tag, 0)] = out * amplitude;
+    }
+}
+
+/// A single sine component of a [`FourierOsc`]. `ratio` is the partial's
+/// frequency as a multiple of the oscillator's `hz` and `phase` is an offset in
+/// cycles, i.e. 0.25 is a quarter of a period.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Partial {
+    pub amplitude: f32,
+    pub ratio: f32,
+    pub phase: f32,
+}
+
+impl Partial {
+    pub fn new(amplitude: f32, ratio: f32, phase: f32) -> Self {
+        Self {
+            amplitude,
+            ratio,
+            phase,
+        }
+    }
+
+    /// The `n`th harmonic with amplitude `amplitude` and no phase offset.
+    pub fn harmonic(n: usize, amplitude: f32) -> Self {
+        Self::new(amplitude, n as f32, 0.0)
+    }
+}
+
+// Each partial occupies `PARTIAL_STRIDE` slots of the module's buffer:
+// amplitude, ratio, phase offset and the running phase.
+const PARTIAL_STRIDE: usize = 4;
+
+/// An additive oscillator. The partials live in the module's buffer rather than
+/// in `State`, so there is no limit on their number and they can be replaced
+/// while the rack is running. Partials above the Nyquist frequency are skipped.
+/// With `lanczos` on, each partial is scaled by the Lanczos sigma factor of its
+/// ratio relative to one past the highest ratio below Nyquist, which tames the
+/// Gibbs ringing of a truncated series.
 #[derive(Clone)]
 pub struct FourierOsc {
     tag: Tag,
-    coefficients: Vec<f32>,
     lanczos: bool,
 }
 
@@ -308,16 +772,14 @@ pub struct FourierOsc {
 pub struct FourierOscBuilder {
     hz: Control,
     amplitude: Control,
-    coefficients: Vec<f32>,
+    partials: Vec<Partial>,
     lanczos: bool,
 }
 
 impl FourierOsc {
-    pub fn new<T: Into<Tag>>(tag: T, coefficients: Vec<f32>, lanczos: bool) -> Self {
-        assert!(coefficients.len() <= 64, "Max size of fourier osc is 64");
+    pub fn new<T: Into<Tag>>(tag: T, lanczos: bool) -> Self {
         FourierOsc {
             tag: tag.into(),
-            coefficients,
             lanczos,
         }
     }
@@ -329,14 +791,70 @@ impl FourierOsc {
     pub fn set_lacnzos(&mut self, value: bool) {
         self.lanczos = value;
     }
+    pub fn num_partials(&self, rack: &Rack) -> usize {
+        rack.buffers.buffers(self.tag).len() / PARTIAL_STRIDE
+    }
+    pub fn partials(&self, rack: &Rack) -> Vec<Partial> {
+        rack.buffers
+            .buffers(self.tag)
+            .as_slice()
+            .chunks(PARTIAL_STRIDE)
+            .map(|p| Partial::new(p[0], p[1], p[2]))
+            .collect()
+    }
+    /// Replace the partials. The running phase of every partial that already
+    /// existed is kept so the change does not click. The buffer only
+    /// reallocates when there are more partials than it has ever held, don't
+    /// grow the set from the audio callback.
+    pub fn set_partials(&self, rack: &mut Rack, partials: &[Partial]) {
+        let buffer = rack.buffers.buffers_mut(self.tag);
+        buffer.resize(partials.len() * PARTIAL_STRIDE);
+        for (p, slot) in partials
+            .iter()
+            .zip(buffer.as_mut_slice().chunks_mut(PARTIAL_STRIDE))
+        {
+            slot[0] = p.amplitude;
+            slot[1] = p.ratio;
+            slot[2] = p.phase;
+        }
+    }
+    /// Replace the partials with harmonics whose amplitudes are `coefficients`,
+    /// where `coefficients[i]` is the amplitude of the `i`th harmonic.
+    pub fn set_coefficients(&self, rack: &mut Rack, coefficients: &[f32]) {
+        self.set_partials(rack, &harmonics(coefficients));
+    }
+    /// Replace the partial at `index`, use `set_partials` to add partials.
+    pub fn set_partial(&self, rack: &mut Rack, index: usize, partial: Partial) {
+        let buffer = rack.buffers.buffers_mut(self.tag);
+        let count = buffer.len() / PARTIAL_STRIDE;
+        assert!(
+            index < count,
+            "Partial index {index} is out of range for {count} partials"
+        );
+        let slot = &mut buffer.as_mut_slice()[index * PARTIAL_STRIDE..(index + 1) * PARTIAL_STRIDE];
+        slot[0] = partial.amplitude;
+        slot[1] = partial.ratio;
+        slot[2] = partial.phase;
+    }
+}
+
+fn harmonics(coefficients: &[f32]) -> Vec<Partial> {
+    coefficients
+        .iter()
+        .enumerate()
+        .map(|(i, c)| Partial::harmonic(i, *c))
+        .collect()
 }
 
 impl FourierOscBuilder {
     pub fn new(coefficients: Vec<f32>) -> Self {
+        Self::from_partials(harmonics(&coefficients))
+    }
+    pub fn from_partials(partials: Vec<Partial>) -> Self {
         Self {
             hz: 0.0.into(),
             amplitude: 1.0.into(),
-            coefficients,
+            partials,
             lanczos: true,
         }
     }
@@ -350,7 +868,12 @@ impl FourierOscBuilder {
         let n = rack.num_modules();
         rack.controls[(n, 0)] = self.hz;
         rack.controls[(n, 1)] = self.amplitude;
-        let osc = Arc::new(FourierOsc::new(n, self.coefficients.clone(), self.lanczos));
+        let osc = Arc::new(FourierOsc::new(n, self.lanczos));
+        rack.buffers.set_buffer(
+            osc.tag,
+            RingBuffer::new(0, vec![0.0; self.partials.len() * PARTIAL_STRIDE]),
+        );
+        osc.set_partials(rack, &self.partials);
         rack.push(osc.clone());
         osc
     }
@@ -366,23 +889,33 @@ fn sinc(x: f32) -> f32 {
 impl Signal for FourierOsc {
     tag!();
     fn signal(&self, rack: &mut Rack, sample_rate: f32) {
-        let tag = self.tag;
         let hz = self.hz(rack);
+        let amplitude = self.amplitude(rack);
+        let nyquist = 0.5 * sample_rate;
         let sigma = self.lanczos as i32;
+        let partials = rack.buffers.buffers_mut(self.tag).as_mut_slice();
+        let top = partials
+            .chunks(PARTIAL_STRIDE)
+            .map(|p| p[1].abs())
+            .filter(|r| (hz * r).abs() < nyquist)
+            .fold(0.0, f32::max)
+            + 1.0;
         let mut out = 0.0;
-        for (i, c) in self.coefficients.iter().enumerate() {
-            out += c
-                * sinc(sigma as f32 * i as f32 / self.coefficients.len() as f32)
-                * (rack.state[(tag, i)] * TAU).sin();
-            rack.state[(tag, i)] += hz * i as f32 / sample_rate;
-            while rack.state[(tag, i)] >= 1.0 {
-                rack.state[(tag, i)] -= 1.0;
+        for p in partials.chunks_mut(PARTIAL_STRIDE) {
+            let partial_hz = hz * p[1];
+            if partial_hz.abs() < nyquist {
+                let taper = sinc(sigma as f32 * p[1].abs() / top);
+                out += p[0] * taper * ((p[3] + p[2]) * TAU).sin();
+            }
+            p[3] += partial_hz / sample_rate;
+            while p[3] >= 1.0 {
+                p[3] -= 1.0;
             }
-            while rack.state[(tag, i)] <= -1.0 {
-                rack.state[(tag, i)] += 1.0;
+            while p[3] <= -1.0 {
+                p[3] += 1.0;
             }
         }
-        rack.outputs[(self.tag, 0)] = out * self.amplitude(rack);
+        rack.outputs[(self.tag, 0)] = out * amplitude;
     }
 }
 
@@ -463,3 +996,363 @@ impl Signal for Clock {
         rack.outputs[(self.tag, 0)] = out;
     }
 }
+
+/// A musical clock driven by a tempo in beats per minute. Each sample a
+/// trigger of 1.0 is written to the output slot of every subdivision that
+/// starts on that sample, 0.0 otherwise:
+/// - 0 - quarter notes (beats)
+/// - 1 - eighth notes
+/// - 2 - sixteenth notes
+/// - 3 - eighth note triplets
+/// - 4 - sixteenth note triplets
+///
+/// Slot 5 holds the running position in beats, slot 6 the position in bars,
+/// `beats_per_bar` is at least 1, and slot 7 the tempo in beats per minute.
+/// `swing` in [0, 1] delays every other eighth and sixteenth note by up to a
+/// quarter of their pair's length. The clock advances while `run` is non-zero
+/// and a rising edge on `reset` returns it to the start.
+#[derive(Copy, Clone)]
+pub struct TempoClock {
+    tag: Tag,
+}
+
+#[derive(Copy, Clone)]
+pub struct TempoClockBuilder {
+    bpm: Control,
+    swing: Control,
+    run: Control,
+    reset: Control,
+    beats_per_bar: Control,
+}
+
+impl TempoClockBuilder {
+    pub fn new<T: Into<Control>>(bpm: T) -> Self {
+        Self {
+            bpm: bpm.into(),
+            swing: 0.0.into(),
+            run: 1.0.into(),
+            reset: 0.0.into(),
+            beats_per_bar: 4.0.into(),
+        }
+    }
+    build!(bpm);
+    build!(swing);
+    build!(run);
+    build!(reset);
+    build!(beats_per_bar);
+    pub fn rack(&self, rack: &mut Rack) -> Arc<TempoClock> {
+        let n = rack.num_modules();
+        rack.controls[(n, 0)] = self.bpm;
+        rack.controls[(n, 1)] = self.swing;
+        rack.controls[(n, 2)] = self.run;
+        rack.controls[(n, 3)] = self.reset;
+        rack.controls[(n, 4)] = self.beats_per_bar;
+        let clock = Arc::new(TempoClock::new(n));
+        clock.reset(rack);
+        // Publish the tempo now so modules synced to the clock can read it
+        // before its first sample.
+        rack.outputs[(n, TempoClock::BPM)] = clock.bpm(rack);
+        rack.push(clock.clone());
+        clock
+    }
+}
+
+// Number of ticks of a division with `per_beat` ticks per beat that have
+// started by `phase` in [0, 1) of the beat. Every second tick is delayed
+// by `swing`.
+fn ticks(phase: f32, per_beat: f32, swing: f32) -> f32 {
+    let pairs = phase * per_beat / 2.0;
+    let f = pairs - pairs.floor();
+    2.0 * pairs.floor() + if f >= 0.5 + 0.25 * swing { 1.0 } else { 0.0 }
+}
+
+impl TempoClock {
+    pub const QUARTER: usize = 0;
+    pub const EIGHTH: usize = 1;
+    pub const SIXTEENTH: usize = 2;
+    pub const EIGHTH_TRIPLET: usize = 3;
+    pub const SIXTEENTH_TRIPLET: usize = 4;
+    pub const BEAT: usize = 5;
+    pub const BAR: usize = 6;
+    pub const BPM: usize = 7;
+
+    pub fn new<T: Into<Tag>>(tag: T) -> Self {
+        Self { tag: tag.into() }
+    }
+    props!(bpm, set_bpm, 0);
+    props!(swing, set_swing, 1);
+    props!(run, set_run, 2);
+    props!(reset_input, set_reset_input, 3);
+    props!(beats_per_bar, set_beats_per_bar, 4);
+
+    pub fn start(&self, rack: &mut Rack) {
+        self.set_run(rack, 1.0.into());
+    }
+
+    pub fn stop(&self, rack: &mut Rack) {
+        self.set_run(rack, 0.0.into());
+    }
+
+    /// Move the clock back to the first beat, it will trigger every
+    /// subdivision on its next sample.
+    pub fn reset(&self, rack: &mut Rack) {
+        rack.state[(self.tag, 0)] = 0.0;
+        rack.state[(self.tag, 1)] = 0.0;
+        rack.state[(self.tag, 3)] = 0.0;
+        rack.state[(self.tag, 4)] = 1.0;
+    }
+}
+
+impl Signal for TempoClock {
+    tag!();
+    fn signal(&self, rack: &mut Rack, sample_rate: f32) {
+        let tag = self.tag;
+        // state: 0 - phase in the current beat, 1 - phase on the previous
+        // sample, 2 - previous reset input, 3 - whole beats, 4 - 1.0 if a new
+        // beat starts on this sample.
+        let reset = self.reset_input(rack);
+        if reset > 0.0 && rack.state[(tag, 2)] <= 0.0 {
+            self.reset(rack);
+        }
+        rack.state[(tag, 2)] = reset;
+        let running = self.run(rack) > 0.0;
+        let swing = self.swing(rack).clamp(0.0, 1.0);
+        let phase = rack.state[(tag, 0)];
+        let prev = rack.state[(tag, 1)];
+        let new_beat = rack.state[(tag, 4)] > 0.0;
+        let divisions = [
+            (1.0, 0.0),
+            (2.0, swing),
+            (4.0, swing),
+            (3.0, 0.0),
+            (6.0, 0.0),
+        ];
+        for (i, (per_beat, swing)) in divisions.iter().enumerate() {
+            let fire =
+                new_beat || ticks(phase, *per_beat, *swing) != ticks(prev, *per_beat, *swing);
+            rack.outputs[(tag, i)] = if running && fire { 1.0 } else { 0.0 };
+        }
+        let beats = rack.state[(tag, 3)] + phase;
+        rack.outputs[(tag, Self::BEAT)] = beats;
+        rack.outputs[(tag, Self::BAR)] = beats / self.beats_per_bar(rack).max(1.0);
+        rack.outputs[(tag, Self::BPM)] = self.bpm(rack);
+        if running {
+            rack.state[(tag, 1)] = phase;
+            rack.state[(tag, 4)] = 0.0;
+            let mut ph = phase + self.bpm(rack) / (60.0 * sample_rate);
+            while ph >= 1.0 {
+                ph -= 1.0;
+                rack.state[(tag, 3)] += 1.0;
+                rack.state[(tag, 1)] = 0.0;
+                rack.state[(tag, 4)] = 1.0;
+            }
+            rack.state[(tag, 0)] = ph;
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum LfoShape {
+    Sine,
+    Triangle,
+    Saw,
+    Square,
+    SampleHold,
+    SmoothRandom,
+}
+
+impl From<LfoShape> for Control {
+    fn from(shape: LfoShape) -> Self {
+        Control::I(shape as usize)
+    }
+}
+
+impl From<usize> for LfoShape {
+    fn from(u: usize) -> Self {
+        match u {
+            0 => LfoShape::Sine,
+            1 => LfoShape::Triangle,
+            2 => LfoShape::Saw,
+            3 => LfoShape::Square,
+            4 => LfoShape::SampleHold,
+            5 => LfoShape::SmoothRandom,
+            _ => panic!("No LfoShape with index {u}"),
+        }
+    }
+}
+
+/// A low frequency oscillator for modulation. The bipolar signal in [-1, 1] is
+/// written to `outputs[0]` and the unipolar signal in [0, 1] to `outputs[1]`,
+/// both scaled by `amplitude`. The rate is `hz` unless the lfo is synced to a
+/// [`TempoClock`], in which case one cycle lasts `beats` beats of the clock. A
+/// rising edge on `gate` restarts the cycle (when not synced) and the fade in,
+/// which ramps the output up from zero over `fade` seconds.
+#[derive(Clone)]
+pub struct Lfo {
+    tag: Tag,
+    rng: NoiseRng,
+}
+
+#[derive(Copy, Clone)]
+pub struct LfoBuilder {
+    hz: Control,
+    amplitude: Control,
+    shape: Control,
+    phase: Control,
+    sync: Control,
+    beats: Control,
+    gate: Control,
+    fade: Control,
+    seed: Option<u64>,
+}
+
+impl LfoBuilder {
+    pub fn new(shape: LfoShape) -> Self {
+        Self {
+            hz: 1.0.into(),
+            amplitude: 1.0.into(),
+            shape: shape.into(),
+            phase: 0.0.into(),
+            sync: false.into(),
+            beats: 1.0.into(),
+            gate: 0.0.into(),
+            fade: 0.0.into(),
+            seed: None,
+        }
+    }
+    build!(hz);
+    build!(amplitude);
+    build!(shape);
+    build!(phase);
+    build!(beats);
+    build!(gate);
+    build!(fade);
+    /// Sync the rate to the beat position of `clock`.
+    pub fn sync(&mut self, clock: Tag) -> &mut Self {
+        self.sync = Control::V(clock, TempoClock::BEAT);
+        self
+    }
+    pub fn seed(&mut self, value: u64) -> &mut Self {
+        self.seed = Some(value);
+        self
+    }
+    pub fn rack(&self, rack: &mut Rack) -> Arc<Lfo> {
+        let n = rack.num_modules();
+        rack.controls[(n, 0)] = self.hz;
+        rack.controls[(n, 1)] = self.amplitude;
+        rack.controls[(n, 2)] = self.shape;
+        rack.controls[(n, 3)] = self.phase;
+        rack.controls[(n, 4)] = self.sync;
+        rack.controls[(n, 5)] = self.beats;
+        rack.controls[(n, 6)] = self.gate;
+        rack.controls[(n, 7)] = self.fade;
+        let lfo = Arc::new(Lfo::new(n, self.seed));
+        lfo.retrigger(rack);
+        rack.push(lfo.clone());
+        lfo
+    }
+}
+
+impl Lfo {
+    pub fn new<T: Into<Tag>>(tag: T, seed: Option<u64>) -> Self {
+        Self {
+            tag: tag.into(),
+            rng: NoiseRng::new(seed),
+        }
+    }
+    props!(hz, set_hz, 0);
+    props!(amplitude, set_amplitude, 1);
+    props!(phase, set_phase, 3);
+    props!(beats, set_beats, 5);
+    props!(gate, set_gate, 6);
+    props!(fade, set_fade, 7);
+
+    pub fn shape(&self, rack: &Rack) -> LfoShape {
+        let inp = rack.controls[(self.tag, 2)];
+        rack.outputs
+            .integer(inp)
+            .expect("shape must be Control::I")
+            .into()
+    }
+    pub fn set_shape(&self, rack: &mut Rack, value: LfoShape) {
+        rack.controls[(self.tag, 2)] = value.into();
+    }
+    pub fn synced(&self, rack: &Rack) -> bool {
+        matches!(rack.controls[(self.tag, 4)], Control::V(..))
+    }
+    pub fn set_sync(&self, rack: &mut Rack, clock: Option<Tag>) {
+        rack.controls[(self.tag, 4)] = match clock {
+            Some(c) => Control::V(c, TempoClock::BEAT),
+            None => false.into(),
+        };
+    }
+
+    /// Restart the cycle and the fade in.
+    pub fn retrigger(&self, rack: &mut Rack) {
+        rack.state[(self.tag, 0)] = 0.0;
+        rack.state[(self.tag, 2)] = 0.0;
+        rack.state[(self.tag, 5)] = 1.0;
+    }
+}
+
+impl Signal for Lfo {
+    tag!();
+    fn signal(&self, rack: &mut Rack, sample_rate: f32) {
+        let tag = self.tag;
+        // state: 0 - phase, 1 - previous gate, 2 - time since the fade in
+        // started, 3 - current random value, 4 - previous random value,
+        // 5 - 1.0 if the lfo was retriggered, 6 - offset phase on the
+        // previous sample.
+        let gate = self.gate(rack);
+        if gate > 0.0 && rack.state[(tag, 1)] <= 0.0 {
+            self.retrigger(rack);
+        }
+        rack.state[(tag, 1)] = gate;
+        let phase = if self.synced(rack) {
+            let beats = rack.outputs.value(rack.controls[(tag, 4)]).unwrap();
+            let cycles = beats / self.beats(rack);
+            cycles - cycles.floor()
+        } else {
+            rack.state[(tag, 0)]
+        };
+        let mut p = phase + self.phase(rack);
+        p -= p.floor();
+        // A new random value is drawn each time the offset phase wraps.
+        let wrapped = (p - rack.state[(tag, 6)]).abs() > 0.5;
+        rack.state[(tag, 6)] = p;
+        if rack.state[(tag, 5)] > 0.0 || wrapped {
+            rack.state[(tag, 4)] = rack.state[(tag, 3)];
+            rack.state[(tag, 3)] = self.rng.white();
+            rack.state[(tag, 5)] = 0.0;
+        }
+        let out = match self.shape(rack) {
+            LfoShape::Sine => sine_osc(p, 0.0),
+            LfoShape::Triangle => triangle_osc(p, 0.0),
+            LfoShape::Saw => saw_osc(p, 0.0),
+            LfoShape::Square => square_osc(p, 0.5),
+            LfoShape::SampleHold => rack.state[(tag, 3)],
+            LfoShape::SmoothRandom => {
+                let x = 0.5 - 0.5 * (p * consts::PI).cos();
+                rack.state[(tag, 4)] + x * (rack.state[(tag, 3)] - rack.state[(tag, 4)])
+            }
+        };
+        let fade = self.fade(rack);
+        let gain = if fade > 0.0 {
+            (rack.state[(tag, 2)] / fade).min(1.0)
+        } else {
+            1.0
+        };
+        rack.state[(tag, 2)] += 1.0 / sample_rate;
+        let amp = self.amplitude(rack) * gain;
+        rack.outputs[(tag, 0)] = amp * out;
+        rack.outputs[(tag, 1)] = amp * 0.5 * (out + 1.0);
+        let mut ph = phase + self.hz(rack) / sample_rate;
+        while ph >= 1.0 {
+            ph -= 1.0;
+        }
+        while ph < 0.0 {
+            ph += 1.0;
+        }
+        rack.state[(tag, 0)] = ph;
+    }
+}
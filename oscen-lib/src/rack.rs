@@ -7,6 +7,9 @@ pub type SignalFn = fn(f32, f32) -> f32;
 pub const MAX_CONTROLS: usize = 32;
 pub const MAX_OUTPUTS: usize = 32;
 pub const MAX_STATE: usize = 64;
+/// The sample rate delay lines are sized for when they are racked, unless the
+/// module's builder is given a different `max_sample_rate`.
+pub const MAX_SAMPLE_RATE: f32 = 96_000.0;
 // Must be changed by hand in Buffers due to limitaion of arr! marcro
 pub const MAX_MODULES: usize = 1024;
 
@@ -245,6 +248,16 @@ where
         self.buffer.len()
     }
 
+    /// The underlying storage in index order, ignoring the write position.
+    /// Useful for modules that keep a variable amount of state in their buffer.
+    pub fn as_slice(&self) -> &[T] {
+        &self.buffer
+    }
+
+    pub fn as_mut_slice(&mut self) -> &mut [T] {
+        &mut self.buffer
+    }
+
     pub fn set_write_pos(&mut self, wp: usize) {
         self.write_pos = wp % self.buffer.len();
     }
@@ -310,13 +323,28 @@ impl RingBuffer {
         let v2 = self.get_offset(delay, 1);
         let v3 = self.get_offset(delay, 2);
         let f = self.read_pos(delay) - self.read_pos(delay).trunc();
-        let a1 = 0.5 * (v2 - v0);
-        let a2 = v0 - 2.5 * v1 + 2.0 * v2 - 0.5 * v3;
-        let a3 = 0.5 * (v3 - v0) + 1.5 * (v1 - v2);
-        a3 * f * f * f + a2 * f * f + a1 * f + v1
+        hermite(v0, v1, v2, v3, f)
+    }
+
+    /// Hermite cubic read of one channel of a buffer holding `channels`
+    /// interleaved delay lines, one frame pushed per sample. `delay` counts
+    /// frames back from the last pushed one, shorter delays are read as 1.
+    pub fn get_cubic_frame(&self, delay: f32, channel: usize, channels: usize) -> f32 {
+        let delay = delay.max(1.0);
+        let k = delay.trunc() as usize;
+        let f = delay - delay.trunc();
+        let v = |j: usize| self.get(((channels - 1 - channel) + j * channels) as f32);
+        hermite(v(k - 1), v(k), v(k + 1), v(k + 2), f)
     }
 }
 
+fn hermite(v0: f32, v1: f32, v2: f32, v3: f32, f: f32) -> f32 {
+    let a1 = 0.5 * (v2 - v0);
+    let a2 = v0 - 2.5 * v1 + 2.0 * v2 - 0.5 * v3;
+    let a3 = 0.5 * (v3 - v0) + 1.5 * (v1 - v2);
+    a3 * f * f * f + a2 * f * f + a1 * f + v1
+}
+
 impl<T> Default for RingBuffer<T>
 where
     T: Default,
@@ -468,4 +496,16 @@ mod tests {
         let result = rb.get_cubic(delay);
         assert_eq!(result, 3.75, "get_cubic returned {}, expected 3.75", result);
     }
+
+    #[test]
+    fn ring_buffer_frames() {
+        let mut rb = RingBuffer::new32(16.0);
+        for i in 0..8 {
+            rb.push(i as f32);
+            rb.push(-(i as f32));
+        }
+        assert_eq!(rb.get_cubic_frame(1.0, 0, 2), 6.0);
+        assert_eq!(rb.get_cubic_frame(2.0, 1, 2), -5.0);
+        assert_eq!(rb.get_cubic_frame(0.25, 0, 2), 6.0);
+    }
 }
@@ -1,8 +1,6 @@
-use crate::filters::*;
-use crate::operators::*;
 use crate::rack::*;
 use crate::{build, props, tag};
-use std::sync::Arc;
+use std::{f32::consts::TAU, sync::Arc};
 
 const FIXED_GAIN: f32 = 0.015;
 
@@ -14,309 +12,485 @@ const OFFSET_ROOM: f32 = 0.7;
 
 const STEREO_SPREAD: usize = 23;
 
-const COMB_TUNING_L1: usize = 1116;
-const COMB_TUNING_R1: usize = 1116 + STEREO_SPREAD;
-const COMB_TUNING_L2: usize = 1188;
-const COMB_TUNING_R2: usize = 1188 + STEREO_SPREAD;
-const COMB_TUNING_L3: usize = 1277;
-const COMB_TUNING_R3: usize = 1277 + STEREO_SPREAD;
-const COMB_TUNING_L4: usize = 1356;
-const COMB_TUNING_R4: usize = 1356 + STEREO_SPREAD;
-const COMB_TUNING_L5: usize = 1422;
-const COMB_TUNING_R5: usize = 1422 + STEREO_SPREAD;
-const COMB_TUNING_L6: usize = 1491;
-const COMB_TUNING_R6: usize = 1491 + STEREO_SPREAD;
-const COMB_TUNING_L7: usize = 1557;
-const COMB_TUNING_R7: usize = 1557 + STEREO_SPREAD;
-const COMB_TUNING_L8: usize = 1617;
-const COMB_TUNING_R8: usize = 1617 + STEREO_SPREAD;
-
-const ALLPASS_TUNING_L1: usize = 556;
-const ALLPASS_TUNING_R1: usize = 556 + STEREO_SPREAD;
-const ALLPASS_TUNING_L2: usize = 441;
-const ALLPASS_TUNING_R2: usize = 441 + STEREO_SPREAD;
-const ALLPASS_TUNING_L3: usize = 341;
-const ALLPASS_TUNING_R3: usize = 341 + STEREO_SPREAD;
-const ALLPASS_TUNING_L4: usize = 225;
-const ALLPASS_TUNING_R4: usize = 225 + STEREO_SPREAD;
+/// Comb and allpass lengths in samples at 44.1 kHz, the right channel adds
+/// `STEREO_SPREAD`. They are scaled for other sample rates.
+const TUNING_SAMPLE_RATE: f32 = 44_100.0;
+const COMB_TUNING: [usize; NUM_COMBS] = [1116, 1188, 1277, 1356, 1422, 1491, 1557, 1617];
+const ALLPASS_TUNING: [usize; NUM_ALLPASSES] = [556, 441, 341, 225];
 
+const NUM_COMBS: usize = 8;
+const NUM_ALLPASSES: usize = 4;
+const NUM_LINES: usize = 2 * (NUM_COMBS + NUM_ALLPASSES);
+
+// State layout: the read/write index of each delay line in `[0, NUM_LINES)`
+// and the comb lowpass stores in `[STORE, STORE + 2 * NUM_COMBS)`. The delay
+// lines live back to back in the module's buffer, combs left, combs right,
+// allpasses left, allpasses right.
+const STORE: usize = NUM_LINES;
+
+/// An implementation of *freeverb*, Jezar's public domain Schroeder-Moorer
+/// reverb: 8 parallel lowpass-feedback combs followed by 4 series allpasses per
+/// channel. Left and right are in output slots 0 and 1. The delay lines are
+/// sized for `max_sample_rate` when racked, above it they keep their length
+/// at that rate.
 #[derive(Clone)]
 pub struct Freeverb {
     tag: Tag,
     wave_l: Tag,
     wave_r: Tag,
-    left: Arc<AllPass>,
-    right: Arc<AllPass>,
+    max_sample_rate: f32,
 }
 
 impl Freeverb {
-    pub fn new<T: Into<Tag>>(
-        tag: T,
-        wave_l: Tag,
-        wave_r: Tag,
-        left: Arc<AllPass>,
-        right: Arc<AllPass>,
-    ) -> Self {
+    pub fn new<T: Into<Tag>>(tag: T, wave_l: Tag, wave_r: Tag, max_sample_rate: f32) -> Self {
         Self {
             tag: tag.into(),
             wave_l,
             wave_r,
-            left,
-            right,
+            max_sample_rate,
         }
     }
 
-    props!(wet_gain_l, set_wet_gain_l, 0);
-    props!(wet_gain_r, set_wet_gain_r, 1);
-    props!(wet, set_wet, 2);
-    props!(width, set_width, 3);
+    props!(room_size, set_room_size, 0);
+    props!(dampening, set_dampening, 1);
+    props!(width, set_width, 2);
+    props!(wet, set_wet, 3);
     props!(dry, set_dry, 4);
-    props!(input_gain, set_input_gain, 5);
-    props!(dampening, set_dampening, 6);
-    props!(room_size, set_room_size, 7);
 
-    pub fn frozen(&self, controls: &Controls, outputs: &Outputs) -> bool {
-        let inp = controls[(self.tag, 8)];
-        outputs.boolean(inp).unwrap()
+    pub fn frozen(&self, rack: &Rack) -> bool {
+        let inp = rack.controls[(self.tag, 5)];
+        rack.outputs.boolean(inp).unwrap()
     }
 
-    pub fn set_frozen(&self, controls: &mut Controls, value: Control) {
-        controls[(self.tag, 8)] = value;
+    pub fn set_frozen(&self, rack: &mut Rack, value: Control) {
+        rack.controls[(self.tag, 5)] = value;
+    }
+
+    fn lengths(sample_rate: f32) -> [usize; NUM_LINES] {
+        let scale = |n: usize| ((n as f32 * sample_rate / TUNING_SAMPLE_RATE) as usize).max(1);
+        let mut lengths = [0; NUM_LINES];
+        for (i, n) in COMB_TUNING.iter().enumerate() {
+            lengths[i] = scale(*n);
+            lengths[NUM_COMBS + i] = scale(n + STEREO_SPREAD);
+        }
+        for (i, n) in ALLPASS_TUNING.iter().enumerate() {
+            lengths[2 * NUM_COMBS + i] = scale(*n);
+            lengths[2 * NUM_COMBS + NUM_ALLPASSES + i] = scale(n + STEREO_SPREAD);
+        }
+        lengths
     }
 }
-// pub fn tick(&mut self, input: (f64, f64)) -> (f64, f64) {
-//     let input_mixed = (input.0 + input.1) * FIXED_GAIN * self.input_gain;
-//
-//     let mut out = (0.0, 0.0);
-//
-//     for combs in self.combs.iter_mut() {
-//         out.0 += combs.0.tick(input_mixed);
-//         out.1 += combs.1.tick(input_mixed);
-//     }
-//
-//     for allpasses in self.allpasses.iter_mut() {
-//         out.0 = allpasses.0.tick(out.0);
-//         out.1 = allpasses.1.tick(out.1);
-//     }
-//
-//     (
-//         out.0 * self.wet_gains.0 + out.1 * self.wet_gains.1 + input.0 * self.dry,
-//         out.1 * self.wet_gains.0 + out.0 * self.wet_gains.1 + input.1 * self.dry,
-//     )
-// }
 
 impl Signal for Freeverb {
     tag!();
 
-    fn signal(
-        &self,
-        controls: &Controls,
-        state: &mut State,
-        outputs: &mut Outputs,
-        buffers: &mut Buffers,
-        sample_rate: f32,
-    ) {
-        let input_l = outputs[(self.wave_l, 0)];
-        let input_r = outputs[(self.wave_r, 0)];
-        let mixed_input = (outputs[(self.wave_l, 0)] + outputs[(self.wave_r, 0)])
-            * FIXED_GAIN
-            * self.input_gain(controls, outputs);
-        outputs[(self.tag, 0)] =
-            self.wet_gain_l(controls, outputs) * outputs[(self.right.tag(), 0)];
-        outputs[(self.tag, 1)] =
-            self.wet_gain_r(controls, outputs) * outputs[(self.right.tag(), 0)];
+    fn signal(&self, rack: &mut Rack, sample_rate: f32) {
+        let input_l = rack.outputs[(self.wave_l, 0)];
+        let input_r = rack.outputs[(self.wave_r, 0)];
+        let (input_gain, feedback, dampening) = if self.frozen(rack) {
+            (0.0, 1.0, 0.0)
+        } else {
+            (
+                1.0,
+                self.room_size(rack) * SCALE_ROOM + OFFSET_ROOM,
+                self.dampening(rack) * SCALE_DAMPENING,
+            )
+        };
+        let wet = self.wet(rack) * SCALE_WET;
+        let width = self.width(rack);
+        let wet_1 = wet * (width / 2.0 + 0.5);
+        let wet_2 = wet * (1.0 - width) / 2.0;
+        let dry = self.dry(rack);
+        let input = (input_l + input_r) * FIXED_GAIN * input_gain;
+
+        let lengths = Self::lengths(sample_rate.min(self.max_sample_rate));
+        let mut starts = [0; NUM_LINES];
+        for i in 1..NUM_LINES {
+            starts[i] = starts[i - 1] + lengths[i - 1];
+        }
+
+        // The indices are wrapped on read as well, in case the lines got
+        // shorter with a change of sample rate.
+        let state = rack.state.state_mut(self.tag);
+        let lines = rack.buffers.buffers_mut(self.tag).as_mut_slice();
+        let mut out = [0.0; 2];
+        for (channel, y) in out.iter_mut().enumerate() {
+            for c in 0..NUM_COMBS {
+                let line = channel * NUM_COMBS + c;
+                let idx = state[line] as usize % lengths[line];
+                let x = &mut lines[starts[line] + idx];
+                let delayed = *x;
+                let store = &mut state[STORE + line];
+                *store = delayed * (1.0 - dampening) + *store * dampening;
+                *x = input + *store * feedback;
+                state[line] = ((idx + 1) % lengths[line]) as f32;
+                *y += delayed;
+            }
+            for a in 0..NUM_ALLPASSES {
+                let line = 2 * NUM_COMBS + channel * NUM_ALLPASSES + a;
+                let idx = state[line] as usize % lengths[line];
+                let x = &mut lines[starts[line] + idx];
+                let delayed = *x;
+                *x = *y + delayed * 0.5;
+                *y = delayed - *y;
+                state[line] = ((idx + 1) % lengths[line]) as f32;
+            }
+        }
+
+        rack.outputs[(self.tag, 0)] = out[0] * wet_1 + out[1] * wet_2 + input_l * dry;
+        rack.outputs[(self.tag, 1)] = out[1] * wet_1 + out[0] * wet_2 + input_r * dry;
     }
 }
 
-// // out.0 * self.wet_gains.0 + out.1 * self.wet_gains.1 + input.0 * self.dry,
-// out.1 * self.wet_gains.0 + out.0 * self.wet_gains.1 + input.1 * self.dry,
-//     std_signal!();
-//     fn signal(&mut self, rack: &Rack, sample_rate: f32) -> f32 {
-//         let inp = rack.output(self.wave);
-//         self.input.lock().value(inp);
-//         let out = self.rack.signal(sample_rate);
-//         self.out = out * self.wet_gain + inp * self.dry;
-//         self.out
-//     }
-// }
-
 pub struct FreeverbBuilder {
     wave_l: Tag,
     wave_r: Tag,
-    wet_gain_l: Control,
-    wet_gain_r: Control,
-    wet: Control,
+    max_sample_rate: f32,
+    room_size: Control,
+    dampening: Control,
     width: Control,
+    wet: Control,
     dry: Control,
-    input_gain: Control,
-    dampening: Control,
-    room_size: Control,
     frozen: Control,
 }
 
 impl FreeverbBuilder {
+    /// Pass the same tag for `wave_l` and `wave_r` to reverberate a mono source.
     pub fn new(wave_l: Tag, wave_r: Tag) -> Self {
         Self {
             wave_l,
             wave_r,
-            wet_gain_l: 0.0.into(),
-            wet_gain_r: 0.0.into(),
-            wet: 0.0.into(),
-            width: 0.0.into(),
+            max_sample_rate: MAX_SAMPLE_RATE,
+            room_size: 0.5.into(),
+            dampening: 0.5.into(),
+            width: 1.0.into(),
+            wet: (1.0 / SCALE_WET).into(),
             dry: 0.0.into(),
-            input_gain: 0.0.into(),
-            dampening: 0.0.into(),
-            room_size: 0.0.into(),
             frozen: false.into(),
         }
     }
-    build!(wet_gain_l);
-    build!(wet_gain_r);
-    build!(wet);
+
+    build!(room_size);
+    build!(dampening);
     build!(width);
+    build!(wet);
     build!(dry);
-    build!(input_gain);
-    build!(dampening);
-    build!(room_size);
     build!(frozen);
 
-    pub fn rack(
-        &self,
-        rack: &mut Rack,
-        controls: &mut Controls,
-        buffers: &mut Buffers,
-    ) -> Arc<Freeverb> {
-        let n = rack.num_modules();
-        controls[(n, 0)] = self.wet_gain_l;
-        controls[(n, 1)] = self.wet_gain_r;
-        controls[(n, 2)] = self.wet;
-        controls[(n, 3)] = self.width;
-        controls[(n, 4)] = self.dry;
-        controls[(n, 5)] = self.input_gain;
-        controls[(n, 6)] = self.dampening;
-        controls[(n, 7)] = self.room_size;
-        controls[(n, 8)] = self.frozen;
-
-        let input = (outputs[(self.wave_l, 0)] + outputs[(self.wave_r, 0)])
-            * FIXED_GAIN
-            * self.input_gain(controls, outputs);
-        let comb1_l = CombBuilder::new(self.wave_l, COMB_TUNING_L1).rack(rack, controls, buffers);
-        let comb1_r = CombBuilder::new(self.wave_r, COMB_TUNING_R1).rack(rack, controls, buffers);
-        let comb2_l = CombBuilder::new(self.wave_l, COMB_TUNING_L2).rack(rack, controls, buffers);
-        let comb2_r = CombBuilder::new(self.wave_r, COMB_TUNING_R2).rack(rack, controls, buffers);
-        let comb3_l = CombBuilder::new(self.wave_l, COMB_TUNING_L3).rack(rack, controls, buffers);
-        let comb3_r = CombBuilder::new(self.wave_r, COMB_TUNING_R3).rack(rack, controls, buffers);
-        let comb4_l = CombBuilder::new(self.wave_l, COMB_TUNING_L4).rack(rack, controls, buffers);
-        let comb4_r = CombBuilder::new(self.wave_r, COMB_TUNING_R4).rack(rack, controls, buffers);
-        let comb5_l = CombBuilder::new(self.wave_l, COMB_TUNING_L5).rack(rack, controls, buffers);
-        let comb5_r = CombBuilder::new(self.wave_r, COMB_TUNING_R5).rack(rack, controls, buffers);
-        let comb6_l = CombBuilder::new(self.wave_l, COMB_TUNING_L6).rack(rack, controls, buffers);
-        let comb6_r = CombBuilder::new(self.wave_r, COMB_TUNING_R6).rack(rack, controls, buffers);
-        let comb7_l = CombBuilder::new(self.wave_l, COMB_TUNING_L7).rack(rack, controls, buffers);
-        let comb7_r = CombBuilder::new(self.wave_r, COMB_TUNING_R7).rack(rack, controls, buffers);
-        let comb8_l = CombBuilder::new(self.wave_l, COMB_TUNING_L8).rack(rack, controls, buffers);
-        let comb8_r = CombBuilder::new(self.wave_r, COMB_TUNING_R8).rack(rack, controls, buffers);
-        let combs_l = MixerBuilder::new(vec![
-            comb1_l.tag(),
-            comb2_l.tag(),
-            comb3_l.tag(),
-            comb4_l.tag(),
-            comb5_l.tag(),
-            comb6_l.tag(),
-            comb7_l.tag(),
-            comb8_l.tag(),
-        ])
-        .rack(rack, controls);
-        let combs_r = MixerBuilder::new(vec![
-            comb1_r.tag(),
-            comb2_r.tag(),
-            comb3_r.tag(),
-            comb4_r.tag(),
-            comb5_r.tag(),
-            comb6_r.tag(),
-            comb7_r.tag(),
-            comb8_r.tag(),
-        ])
-        .rack(rack, controls);
-        let all1_l = AllPassBuilder::new(combs_l.tag(), ALLPASS_TUNING_L1).rack(rack, buffers);
-        let all1_r = AllPassBuilder::new(combs_r.tag(), ALLPASS_TUNING_R1).rack(rack, buffers);
-        let all2_l = AllPassBuilder::new(all1_l.tag(), ALLPASS_TUNING_L2).rack(rack, buffers);
-        let all2_r = AllPassBuilder::new(all1_r.tag(), ALLPASS_TUNING_R2).rack(rack, buffers);
-        let all3_l = AllPassBuilder::new(all2_l.tag(), ALLPASS_TUNING_L3).rack(rack, buffers);
-        let all3_r = AllPassBuilder::new(all2_r.tag(), ALLPASS_TUNING_R3).rack(rack, buffers);
-        let all4_l = AllPassBuilder::new(all3_l.tag(), ALLPASS_TUNING_L4).rack(rack, buffers);
-        let all4_r = AllPassBuilder::new(all3_r.tag(), ALLPASS_TUNING_R4).rack(rack, buffers);
+    /// The highest sample rate the delay lines are sized for, defaults to
+    /// [`MAX_SAMPLE_RATE`].
+    pub fn max_sample_rate(&mut self, value: f32) -> &mut Self {
+        self.max_sample_rate = value;
+        self
+    }
+
+    pub fn rack(&self, rack: &mut Rack) -> Arc<Freeverb> {
         let n = rack.num_modules();
-        let fv = Arc::new(Freeverb::new(n, self.wave_l, self.wave_r, all4_l, all4_r));
+        rack.controls[(n, 0)] = self.room_size;
+        rack.controls[(n, 1)] = self.dampening;
+        rack.controls[(n, 2)] = self.width;
+        rack.controls[(n, 3)] = self.wet;
+        rack.controls[(n, 4)] = self.dry;
+        rack.controls[(n, 5)] = self.frozen;
+        let fv = Arc::new(Freeverb::new(
+            n,
+            self.wave_l,
+            self.wave_r,
+            self.max_sample_rate,
+        ));
+        let total = Freeverb::lengths(self.max_sample_rate).iter().sum();
+        rack.buffers
+            .set_buffer(fv.tag, RingBuffer::new(0, vec![0.0; total]));
         rack.push(fv.clone());
         fv
     }
 }
 
-// impl Freeverb {
-// pub fn wave(&mut self, arg: Tag) -> &mut Self {
-//     self.wave = arg;
-//     self
-// }
-
-// pub fn dampening(&mut self, value: f32) -> &mut Self {
-//     self.dampening = value * SCALE_DAMPENING;
-//     self.update_combs();
-//     self
-// }
-
-// pub fn freeze(&mut self, frozen: bool) -> &mut Self {
-//     self.frozen = frozen;
-//     self.update_combs();
-//     self
-// }
-
-// pub fn wet(&mut self, value: f32) -> &mut Self {
-//     self.wet = value * SCALE_WET;
-//     self.update_wet_gains();
-//     self
-// }
-
-// pub fn width(&mut self, value: f32) -> &mut Self {
-//     self.width = value;
-//     self.update_wet_gains();
-//     self
-// }
-
-// fn update_wet_gains(&mut self) {
-//     self.wet_gain = self.wet * (self.width / 2.0 + 0.5);
-// }
-
-// pub fn frozen(&mut self, frozen: bool) -> &mut Self {
-//     self.frozen = frozen;
-//     self.input_gain = if frozen { 0.0 } else { 1.0 };
-//     self.update_combs();
-//     self
-// }
-
-// pub fn room_size(&mut self, value: f32) -> &mut Self {
-//     self.room_size = value * SCALE_ROOM + OFFSET_ROOM;
-//     self.update_combs();
-//     self
-// }
-
-// fn update_combs(&mut self) {
-//     let (feedback, dampening) = if self.frozen {
-//         (1.0, 0.0)
-//     } else {
-//         (self.room_size, self.dampening)
-//     };
-
-//     for o in self.rack.0.clone().iter_mut() {
-//         if let Some(v) = o.as_any_mut().downcast_mut::<Comb>() {
-//             v.feedback(feedback);
-//             v.dampening(dampening);
-//         }
-//     }
-// }
-
-// pub fn dry(&mut self, value: f32) -> &mut Self {
-//     self.dry = value;
-//     self
-// }
-// }
+/// Delay line lengths in samples at 44.1 kHz, all prime. An 8 line network
+/// uses the first 8.
+const FDN_TUNING: [usize; 16] = [
+    1307, 1637, 1811, 1931, 2111, 2221, 2381, 2617, 1381, 1543, 1753, 1993, 2161, 2333, 2477, 2719,
+];
+const FDN_MAX_LINES: usize = 16;
+/// The longest modulation depth in seconds.
+const FDN_MAX_MOD: f32 = 0.005;
+
+// State layout: the damping filter store of each line in `[0, FDN_MAX_LINES)`,
+// then the modulation lfo phase.
+const FDN_PHASE: usize = FDN_MAX_LINES;
+
+/// The orthogonal feedback matrix of an [`FdnReverb`].
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum FdnMatrix {
+    /// Maximally dense mixing, every line feeds every other with equal weight.
+    Hadamard,
+    /// Reflection about the all ones vector, cheaper and less diffuse.
+    Householder,
+}
+
+impl FdnMatrix {
+    /// Mix `xs` in place, the length of `xs` must be a power of 2.
+    fn mix(&self, xs: &mut [f32]) {
+        let n = xs.len();
+        match self {
+            FdnMatrix::Hadamard => {
+                let mut h = 1;
+                while h < n {
+                    for i in (0..n).step_by(2 * h) {
+                        for j in i..i + h {
+                            let (a, b) = (xs[j], xs[j + h]);
+                            xs[j] = a + b;
+                            xs[j + h] = a - b;
+                        }
+                    }
+                    h *= 2;
+                }
+                let scale = 1.0 / (n as f32).sqrt();
+                xs.iter_mut().for_each(|x| *x *= scale);
+            }
+            FdnMatrix::Householder => {
+                let d = 2.0 / n as f32 * xs.iter().sum::<f32>();
+                xs.iter_mut().for_each(|x| *x -= d);
+            }
+        }
+    }
+}
+
+/// A feedback delay network reverb with 8 or 16 lines.
+///
+/// Each line has a one pole lowpass for `damping` and a gain chosen so that
+/// the network decays by 60 dB in `decay` seconds. Line lengths are slowly
+/// modulated by `mod_depth` seconds at `mod_rate` Hz to avoid metallic ringing.
+/// Left and right are in output slots 0 and 1.
+///
+/// The lines, plus a left and right pre-delay line, are interleaved frame by
+/// frame in the module's `RingBuffer`, which is sized for `max_sample_rate`
+/// when racked. Above that rate the lines keep their length at that rate.
+#[derive(Clone)]
+pub struct FdnReverb {
+    tag: Tag,
+    wave_l: Tag,
+    wave_r: Tag,
+    lines: usize,
+    matrix: FdnMatrix,
+    max_pre_delay: f32,
+    max_sample_rate: f32,
+}
+
+impl FdnReverb {
+    pub fn new<T: Into<Tag>>(
+        tag: T,
+        wave_l: Tag,
+        wave_r: Tag,
+        lines: usize,
+        matrix: FdnMatrix,
+        max_pre_delay: f32,
+        max_sample_rate: f32,
+    ) -> Self {
+        assert!(
+            lines == 8 || lines == 16,
+            "FdnReverb needs 8 or 16 lines, not {lines}"
+        );
+        Self {
+            tag: tag.into(),
+            wave_l,
+            wave_r,
+            lines,
+            matrix,
+            max_pre_delay,
+            max_sample_rate,
+        }
+    }
+
+    props!(decay, set_decay, 0);
+    props!(damping, set_damping, 1);
+    props!(pre_delay, set_pre_delay, 2);
+    props!(mod_depth, set_mod_depth, 3);
+    props!(mod_rate, set_mod_rate, 4);
+    props!(wet, set_wet, 5);
+    props!(dry, set_dry, 6);
+
+    pub fn lines(&self) -> usize {
+        self.lines
+    }
+
+    pub fn matrix(&self) -> FdnMatrix {
+        self.matrix
+    }
+
+    /// Samples per frame, one for each line plus the two pre-delay lines.
+    fn channels(&self) -> usize {
+        self.lines + 2
+    }
+
+    fn length(i: usize, sample_rate: f32) -> f32 {
+        FDN_TUNING[i] as f32 * sample_rate / TUNING_SAMPLE_RATE
+    }
+
+    fn frames(&self, sample_rate: f32) -> usize {
+        let longest = FDN_TUNING[..self.lines].iter().max().unwrap();
+        let line = *longest as f32 * sample_rate / TUNING_SAMPLE_RATE + FDN_MAX_MOD * sample_rate;
+        let pre = self.max_pre_delay * sample_rate;
+        line.max(pre) as usize + 4
+    }
+
+    /// Read `channel` delayed by `delay` samples with cubic interpolation.
+    fn tap(&self, buffer: &RingBuffer, channel: usize, delay: f32, frames: usize) -> f32 {
+        // The frame pushed on the previous sample is 1 sample old.
+        let age = (delay - 1.0).clamp(1.0, frames as f32 - 3.0);
+        buffer.get_cubic_frame(age, channel, self.channels())
+    }
+}
+
+impl Signal for FdnReverb {
+    tag!();
+
+    fn signal(&self, rack: &mut Rack, sample_rate: f32) {
+        let tag = self.tag;
+        let n = self.lines;
+        let line_rate = sample_rate.min(self.max_sample_rate);
+        let frames = rack.buffers.buffers(tag).len() / self.channels();
+        let input_l = rack.outputs[(self.wave_l, 0)];
+        let input_r = rack.outputs[(self.wave_r, 0)];
+        let decay = self.decay(rack).max(0.01);
+        let damping = self.damping(rack).clamp(0.0, 0.99);
+        let pre_delay = self.pre_delay(rack) * sample_rate;
+        let depth = self.mod_depth(rack).clamp(0.0, FDN_MAX_MOD) * sample_rate;
+        let phase = rack.state[(tag, FDN_PHASE)];
+
+        let buffer = rack.buffers.buffers(tag);
+        let pre_l = self.tap(buffer, n, pre_delay, frames);
+        let pre_r = self.tap(buffer, n + 1, pre_delay, frames);
+        let mut xs = [0.0; FDN_MAX_LINES];
+        for (i, x) in xs[..n].iter_mut().enumerate() {
+            let lfo = (TAU * (phase + i as f32 / n as f32)).sin();
+            let length = Self::length(i, line_rate) + depth * (1.0 + lfo) / 2.0;
+            *x = self.tap(buffer, i, length, frames);
+        }
+
+        let (mut wet_l, mut wet_r) = (0.0, 0.0);
+        for (i, x) in xs[..n].iter_mut().enumerate() {
+            if i % 2 == 0 {
+                wet_l += *x;
+            } else {
+                wet_r += *x;
+            }
+            let store = &mut rack.state[(tag, i)];
+            *store = (1.0 - damping) * *x + damping * *store;
+            // Gain for a 60 dB drop over `decay` seconds.
+            let length = Self::length(i, line_rate);
+            *x = *store * 10f32.powf(-3.0 * length / (decay * sample_rate));
+        }
+        self.matrix.mix(&mut xs[..n]);
+
+        let buffer = rack.buffers.buffers_mut(tag);
+        for (i, x) in xs[..n].iter().enumerate() {
+            let sign = if (i / 2) % 2 == 0 { 1.0 } else { -1.0 };
+            let input = if i % 2 == 0 { pre_l } else { pre_r };
+            buffer.push(x + sign * input);
+        }
+        buffer.push(input_l);
+        buffer.push(input_r);
+
+        rack.state[(tag, FDN_PHASE)] = (phase + self.mod_rate(rack) / sample_rate).fract();
+        let scale = (2.0 / n as f32).sqrt();
+        let wet = self.wet(rack) * scale;
+        let dry = self.dry(rack);
+        rack.outputs[(tag, 0)] = wet_l * wet + input_l * dry;
+        rack.outputs[(tag, 1)] = wet_r * wet + input_r * dry;
+    }
+}
+
+pub struct FdnReverbBuilder {
+    wave_l: Tag,
+    wave_r: Tag,
+    lines: usize,
+    matrix: FdnMatrix,
+    max_pre_delay: f32,
+    max_sample_rate: f32,
+    decay: Control,
+    damping: Control,
+    pre_delay: Control,
+    mod_depth: Control,
+    mod_rate: Control,
+    wet: Control,
+    dry: Control,
+}
+
+impl FdnReverbBuilder {
+    /// Pass the same tag for `wave_l` and `wave_r` to reverberate a mono source.
+    pub fn new(wave_l: Tag, wave_r: Tag) -> Self {
+        Self {
+            wave_l,
+            wave_r,
+            lines: 8,
+            matrix: FdnMatrix::Hadamard,
+            max_pre_delay: 0.25,
+            max_sample_rate: MAX_SAMPLE_RATE,
+            decay: 2.0.into(),
+            damping: 0.3.into(),
+            pre_delay: 0.02.into(),
+            mod_depth: 0.0005.into(),
+            mod_rate: 0.5.into(),
+            wet: 0.5.into(),
+            dry: 0.5.into(),
+        }
+    }
+
+    build!(decay);
+    build!(damping);
+    build!(pre_delay);
+    build!(mod_depth);
+    build!(mod_rate);
+    build!(wet);
+    build!(dry);
+
+    /// The number of delay lines, 8 or 16.
+    pub fn lines(&mut self, value: usize) -> &mut Self {
+        self.lines = value;
+        self
+    }
+
+    pub fn matrix(&mut self, value: FdnMatrix) -> &mut Self {
+        self.matrix = value;
+        self
+    }
+
+    /// The longest pre-delay in seconds, defaults to 0.25.
+    pub fn max_pre_delay(&mut self, value: f32) -> &mut Self {
+        self.max_pre_delay = value;
+        self
+    }
+
+    /// The highest sample rate the delay lines are sized for, defaults to
+    /// [`MAX_SAMPLE_RATE`].
+    pub fn max_sample_rate(&mut self, value: f32) -> &mut Self {
+        self.max_sample_rate = value;
+        self
+    }
+
+    pub fn rack(&self, rack: &mut Rack) -> Arc<FdnReverb> {
+        let n = rack.num_modules();
+        rack.controls[(n, 0)] = self.decay;
+        rack.controls[(n, 1)] = self.damping;
+        rack.controls[(n, 2)] = self.pre_delay;
+        rack.controls[(n, 3)] = self.mod_depth;
+        rack.controls[(n, 4)] = self.mod_rate;
+        rack.controls[(n, 5)] = self.wet;
+        rack.controls[(n, 6)] = self.dry;
+        let fdn = Arc::new(FdnReverb::new(
+            n,
+            self.wave_l,
+            self.wave_r,
+            self.lines,
+            self.matrix,
+            self.max_pre_delay,
+            self.max_sample_rate,
+        ));
+        let len = fdn.frames(self.max_sample_rate) * fdn.channels();
+        rack.buffers
+            .set_buffer(fdn.tag, RingBuffer::new(0, vec![0.0; len]));
+        rack.push(fdn.clone());
+        fdn
+    }
+}
@@ -1,5 +1,6 @@
+use crate::oscillators::NoiseRng;
 use crate::rack::*;
-use crate::{props, tag};
+use crate::{build, props, tag};
 use std::f32::consts::PI;
 use std::sync::Arc;
 
@@ -84,3 +85,308 @@ impl TanhBuilder {
         t
     }
 }
+
+/// The transfer curves of a [`Distortion`].
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum DistortionCurve {
+    /// Clip at ±1.
+    Hard,
+    /// `tanh` saturation.
+    Soft,
+    /// Exponential saturation that clips the negative half earlier, adding
+    /// even harmonics.
+    Tube,
+    /// Fold anything beyond ±1 back into range.
+    Foldback,
+}
+
+impl DistortionCurve {
+    pub fn shape(&self, x: f32) -> f32 {
+        match self {
+            DistortionCurve::Hard => x.clamp(-1.0, 1.0),
+            DistortionCurve::Soft => x.tanh(),
+            DistortionCurve::Tube => {
+                if x >= 0.0 {
+                    1.0 - (-x).exp()
+                } else {
+                    -0.8 * (1.0 - (x / 0.8).exp())
+                }
+            }
+            DistortionCurve::Foldback => {
+                let y = (x + 1.0).rem_euclid(4.0);
+                if y < 2.0 {
+                    y - 1.0
+                } else {
+                    3.0 - y
+                }
+            }
+        }
+    }
+}
+
+impl From<DistortionCurve> for Control {
+    fn from(curve: DistortionCurve) -> Self {
+        Control::I(curve as usize)
+    }
+}
+
+impl From<usize> for DistortionCurve {
+    fn from(u: usize) -> Self {
+        match u {
+            0 => DistortionCurve::Hard,
+            1 => DistortionCurve::Soft,
+            2 => DistortionCurve::Tube,
+            3 => DistortionCurve::Foldback,
+            _ => panic!("No DistortionCurve with index {u}"),
+        }
+    }
+}
+
+/// Drive the input into one of the [`DistortionCurve`]s. `drive` multiplies
+/// the input and `bias` is added to it before shaping to make the curve
+/// asymmetric. The offset this causes is removed, so silence stays silent.
+#[derive(Debug, Copy, Clone)]
+pub struct Distortion {
+    tag: Tag,
+    wave: Tag,
+}
+
+impl Distortion {
+    pub fn new(tag: Tag, wave: Tag) -> Self {
+        Self { tag, wave }
+    }
+
+    props!(drive, set_drive, 0);
+    props!(bias, set_bias, 1);
+
+    pub fn curve(&self, rack: &Rack) -> DistortionCurve {
+        let inp = rack.controls[(self.tag, 2)];
+        rack.outputs
+            .integer(inp)
+            .expect("curve must be Control::I")
+            .into()
+    }
+
+    pub fn set_curve(&self, rack: &mut Rack, value: DistortionCurve) {
+        rack.controls[(self.tag, 2)] = value.into();
+    }
+}
+
+impl Signal for Distortion {
+    tag!();
+
+    fn signal(&self, rack: &mut Rack, _sample_rate: f32) {
+        let curve = self.curve(rack);
+        let bias = self.bias(rack);
+        let x = self.drive(rack) * rack.outputs[(self.wave, 0)] + bias;
+        rack.outputs[(self.tag, 0)] = curve.shape(x) - curve.shape(bias);
+    }
+}
+
+#[derive(Debug, Copy, Clone)]
+pub struct DistortionBuilder {
+    wave: Tag,
+    drive: Control,
+    bias: Control,
+    curve: Control,
+}
+
+impl DistortionBuilder {
+    pub fn new(wave: Tag, curve: DistortionCurve) -> Self {
+        Self {
+            wave,
+            drive: 1.0.into(),
+            bias: 0.0.into(),
+            curve: curve.into(),
+        }
+    }
+
+    build!(drive);
+    build!(bias);
+    build!(curve);
+
+    pub fn rack(&self, rack: &mut Rack) -> Arc<Distortion> {
+        let n = rack.num_modules();
+        rack.controls[(n, 0)] = self.drive;
+        rack.controls[(n, 1)] = self.bias;
+        rack.controls[(n, 2)] = self.curve;
+        let d = Arc::new(Distortion::new(n.into(), self.wave));
+        rack.push(d.clone());
+        d
+    }
+}
+
+/// Reduce the bit depth and sample rate of the input. The input, assumed to
+/// be in [-1, 1], is quantized to `bits` bits (fractional values are allowed)
+/// and held for `rate` Hz. With `dither` on, triangular noise of one step is
+/// added before quantizing, trading the distortion for noise.
+pub struct BitCrusher {
+    tag: Tag,
+    wave: Tag,
+    rng: NoiseRng,
+}
+
+impl BitCrusher {
+    pub fn new(tag: Tag, wave: Tag, seed: Option<u64>) -> Self {
+        Self {
+            tag,
+            wave,
+            rng: NoiseRng::new(seed),
+        }
+    }
+
+    props!(bits, set_bits, 0);
+    props!(rate, set_rate, 1);
+
+    pub fn dither(&self, rack: &Rack) -> bool {
+        let inp = rack.controls[(self.tag, 2)];
+        rack.outputs.boolean(inp).unwrap()
+    }
+
+    pub fn set_dither(&self, rack: &mut Rack, value: Control) {
+        rack.controls[(self.tag, 2)] = value;
+    }
+}
+
+impl Signal for BitCrusher {
+    tag!();
+
+    fn signal(&self, rack: &mut Rack, sample_rate: f32) {
+        // state: 0 - time left until the next sample, in periods of `rate`,
+        // 1 - held value.
+        let mut countdown = rack.state[(self.tag, 0)];
+        if countdown <= 0.0 {
+            countdown += 1.0;
+            let step = 2f32.powf(1.0 - self.bits(rack).max(1.0));
+            let mut x = rack.outputs[(self.wave, 0)];
+            if self.dither(rack) {
+                x += 0.5 * step * (self.rng.white() + self.rng.white());
+            }
+            rack.state[(self.tag, 1)] = ((x / step).round() * step).clamp(-1.0, 1.0);
+        }
+        rack.state[(self.tag, 0)] = countdown - self.rate(rack) / sample_rate;
+        rack.outputs[(self.tag, 0)] = rack.state[(self.tag, 1)];
+    }
+}
+
+#[derive(Debug, Copy, Clone)]
+pub struct BitCrusherBuilder {
+    wave: Tag,
+    bits: Control,
+    rate: Control,
+    dither: Control,
+    seed: Option<u64>,
+}
+
+impl BitCrusherBuilder {
+    pub fn new(wave: Tag) -> Self {
+        Self {
+            wave,
+            bits: 8.0.into(),
+            rate: 44_100.0.into(),
+            dither: false.into(),
+            seed: None,
+        }
+    }
+
+    build!(bits);
+    build!(rate);
+    build!(dither);
+
+    pub fn seed(&mut self, value: u64) -> &mut Self {
+        self.seed = Some(value);
+        self
+    }
+
+    pub fn rack(&self, rack: &mut Rack) -> Arc<BitCrusher> {
+        let n = rack.num_modules();
+        rack.controls[(n, 0)] = self.bits;
+        rack.controls[(n, 1)] = self.rate;
+        rack.controls[(n, 2)] = self.dither;
+        let bc = Arc::new(BitCrusher::new(n.into(), self.wave, self.seed));
+        rack.push(bc.clone());
+        bc
+    }
+}
+
+/// Map the input through a user supplied transfer curve. The table spans
+/// inputs from -1 to 1 in equal steps and is read with linear interpolation;
+/// inputs beyond ±1 take the end values. `drive` multiplies the input. The
+/// table lives in the rack so it can be replaced while running.
+#[derive(Debug, Copy, Clone)]
+pub struct Waveshaper {
+    tag: Tag,
+    wave: Tag,
+}
+
+impl Waveshaper {
+    pub fn new(tag: Tag, wave: Tag) -> Self {
+        Self { tag, wave }
+    }
+
+    props!(drive, set_drive, 0);
+
+    pub fn table(&self, rack: &Rack) -> Vec<f32> {
+        rack.buffers.buffers(self.tag).as_slice().to_vec()
+    }
+
+    pub fn set_table(&self, rack: &mut Rack, table: &[f32]) {
+        assert!(table.len() >= 2, "a transfer curve needs at least 2 points");
+        let buffer = rack.buffers.buffers_mut(self.tag);
+        buffer.resize(table.len());
+        buffer.as_mut_slice().copy_from_slice(table);
+    }
+}
+
+impl Signal for Waveshaper {
+    tag!();
+
+    fn signal(&self, rack: &mut Rack, _sample_rate: f32) {
+        let x = self.drive(rack) * rack.outputs[(self.wave, 0)];
+        let table = rack.buffers.buffers(self.tag).as_slice();
+        let last = table.len() - 1;
+        let pos = (x.clamp(-1.0, 1.0) + 1.0) / 2.0 * last as f32;
+        let i = (pos.trunc() as usize).min(last - 1);
+        let f = pos - i as f32;
+        rack.outputs[(self.tag, 0)] = (1.0 - f) * table[i] + f * table[i + 1];
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct WaveshaperBuilder {
+    wave: Tag,
+    table: Vec<f32>,
+    drive: Control,
+}
+
+impl WaveshaperBuilder {
+    pub fn new(wave: Tag, table: Vec<f32>) -> Self {
+        assert!(table.len() >= 2, "a transfer curve needs at least 2 points");
+        Self {
+            wave,
+            table,
+            drive: 1.0.into(),
+        }
+    }
+
+    /// Tabulate `f` at `points` equally spaced inputs from -1 to 1.
+    pub fn from_fn<F: Fn(f32) -> f32>(wave: Tag, f: F, points: usize) -> Self {
+        assert!(points >= 2, "a transfer curve needs at least 2 points");
+        let table = (0..points)
+            .map(|i| f(2.0 * i as f32 / (points - 1) as f32 - 1.0))
+            .collect();
+        Self::new(wave, table)
+    }
+
+    build!(drive);
+
+    pub fn rack(&self, rack: &mut Rack) -> Arc<Waveshaper> {
+        let n = rack.num_modules();
+        rack.controls[(n, 0)] = self.drive;
+        let ws = Arc::new(Waveshaper::new(n.into(), self.wave));
+        rack.buffers
+            .set_buffer(ws.tag, RingBuffer::new(0, self.table.clone()));
+        rack.push(ws.clone());
+        ws
+    }
+}
@@ -1,5 +1,7 @@
+use crate::oscillators::ConstBuilder;
 use crate::rack::*;
 use approx::relative_eq;
+use std::f32::consts::PI;
 
 /// Given f(0) = low, f(1/2) = mid, and f(1) = high, let f(x) = a + b*exp(cs).
 /// Fit a, b, and c so to match the above. If mid < 1/2(high + low) then f is
@@ -34,6 +36,58 @@ pub fn signals(rack: &mut Rack, start: u32, end: u32, sample_rate: f32) -> Vec<(
     result
 }
 
+/// `points` logarithmically spaced frequencies from `low` to `high` Hz.
+pub fn log_frequencies(low: f32, high: f32, points: usize) -> Vec<f32> {
+    let (low, high) = (low.ln(), high.ln());
+    (0..points)
+        .map(|i| (low + (high - low) * i as f32 / (points.max(2) - 1) as f32).exp())
+        .collect()
+}
+
+/// The first `length` samples of the impulse response of a single input
+/// module. `build` adds the module to a temporary rack, given the tag of its
+/// input, and returns the tag of the module whose output is measured.
+pub fn impulse_response<F>(build: F, length: usize, sample_rate: f32) -> Vec<f32>
+where
+    F: FnOnce(&mut Rack, Tag) -> Tag,
+{
+    let mut rack = Rack::new();
+    let impulse = ConstBuilder::new(1.0.into()).rack(&mut rack);
+    let out = build(&mut rack, impulse.tag());
+    let mut result = Vec::with_capacity(length);
+    for _ in 0..length {
+        rack.play(sample_rate);
+        impulse.set_value(&mut rack, 0.0.into());
+        result.push(rack.outputs[(out, 0)]);
+    }
+    result
+}
+
+/// Measure the frequency response of a single input module, see
+/// [`impulse_response`], at each of `frequencies`. One second of the impulse
+/// response is transformed, returning (Hz, magnitude in dB, phase in radians).
+pub fn frequency_response<F>(
+    build: F,
+    frequencies: &[f32],
+    sample_rate: f32,
+) -> Vec<(f32, f32, f32)>
+where
+    F: FnOnce(&mut Rack, Tag) -> Tag,
+{
+    let h = impulse_response(build, sample_rate as usize, sample_rate);
+    frequencies
+        .iter()
+        .map(|hz| {
+            let w = 2.0 * PI * hz / sample_rate;
+            let (re, im) = h.iter().enumerate().fold((0.0, 0.0), |(re, im), (n, x)| {
+                let (sin, cos) = (w * n as f32).sin_cos();
+                (re + x * cos, im - x * sin)
+            });
+            (*hz, 20.0 * re.hypot(im).log10(), im.atan2(re))
+        })
+        .collect()
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
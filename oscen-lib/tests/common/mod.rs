@@ -0,0 +1,12 @@
+//! Analysis helpers shared by the integration tests.
+
+/// Magnitude of the `hz` component of `xs`.
+pub fn dft(xs: &[f32], hz: f32, sample_rate: f32) -> f32 {
+    let (mut re, mut im) = (0.0f64, 0.0f64);
+    for (n, x) in xs.iter().enumerate() {
+        let w = 2.0 * std::f64::consts::PI * hz as f64 * n as f64 / sample_rate as f64;
+        re += *x as f64 * w.cos();
+        im -= *x as f64 * w.sin();
+    }
+    (2.0 * (re * re + im * im).sqrt() / xs.len() as f64) as f32
+}
@@ -0,0 +1,618 @@
+mod common;
+
+use common::dft;
+use oscen::effects::*;
+use oscen::oscillators::*;
+use oscen::rack::*;
+use oscen::utils::impulse_response;
+use std::f32::consts::PI;
+
+#[test]
+fn chorus_static_delay() {
+    let sr = 1000.0;
+    let mut rack = Rack::default();
+    let imp = ConstBuilder::new(1.0.into()).rack(&mut rack);
+    let chorus = ChorusBuilder::new(imp.tag())
+        .voices(1)
+        .rate(0.0)
+        .depth(0.0)
+        .delay(0.005)
+        .mix(1.0)
+        .rack(&mut rack);
+    let mut out = vec![];
+    for _ in 0..8 {
+        rack.play(sr);
+        imp.set_value(&mut rack, 0.0.into());
+        let o = rack.outputs.outputs(chorus.tag());
+        out.push((o[0], o[1]));
+    }
+    for (i, (l, r)) in out.iter().enumerate() {
+        let expected = if i == 5 { 1.0 } else { 0.0 };
+        assert!((l - expected).abs() < 1e-6, "{out:?}");
+        assert!((r - expected).abs() < 1e-6, "{out:?}");
+    }
+}
+
+#[test]
+fn chorus_stereo() {
+    let sr = 44100.0;
+    let mut rack = Rack::default();
+    let sine = OscBuilder::new(sine_osc).hz(220.0).rack(&mut rack);
+    let chorus = ChorusBuilder::new(sine.tag()).voices(4).rack(&mut rack);
+    let mut differ = false;
+    for _ in 0..4410 {
+        rack.play(sr);
+        let o = rack.outputs.outputs(chorus.tag());
+        differ |= (o[0] - o[1]).abs() > 1e-3;
+    }
+    assert!(differ);
+
+    chorus.set_spread(&mut rack, 0.0.into());
+    for _ in 0..100 {
+        rack.play(sr);
+        let o = rack.outputs.outputs(chorus.tag());
+        assert!((o[0] - o[1]).abs() < 1e-6);
+    }
+}
+
+fn flanger(invert: bool) -> Vec<f32> {
+    impulse_response(
+        |rack, input| {
+            FlangerBuilder::new(input)
+                .rate(0.0)
+                .depth(0.0)
+                .delay(0.005)
+                .feedback(0.5)
+                .mix(1.0)
+                .invert(invert)
+                .rack(rack)
+                .tag()
+        },
+        16,
+        1000.0,
+    )
+}
+
+#[test]
+fn flanger_feedback() {
+    let h = flanger(false);
+    let mut expected = [0.0; 16];
+    expected[5] = 1.0;
+    expected[10] = 0.5;
+    expected[15] = 0.25;
+    for (x, e) in h.iter().zip(expected.iter()) {
+        assert!((x - e).abs() < 1e-6, "{h:?}");
+    }
+}
+
+#[test]
+fn flanger_invert() {
+    let h = flanger(true);
+    assert!((h[5] + 1.0).abs() < 1e-6, "{h:?}");
+    assert!((h[10] - 0.5).abs() < 1e-6, "{h:?}");
+    assert!((h[15] + 0.25).abs() < 1e-6, "{h:?}");
+}
+
+#[test]
+fn flanger_sweeps() {
+    let sr = 44100.0;
+    let mut rack = Rack::default();
+    let sine = OscBuilder::new(sine_osc).hz(1000.0).rack(&mut rack);
+    let flanger = FlangerBuilder::new(sine.tag())
+        .rate(5.0)
+        .feedback(0.0)
+        .rack(&mut rack);
+    // The swept notch makes the output level move over an lfo cycle.
+    let mut levels = vec![];
+    for _ in 0..20 {
+        let mut e = 0.0;
+        for _ in 0..441 {
+            rack.play(sr);
+            e += rack.outputs[(flanger.tag(), 0)].powi(2);
+        }
+        levels.push(e);
+    }
+    let max = levels.iter().cloned().fold(0.0, f32::max);
+    let min = levels.iter().cloned().fold(f32::MAX, f32::min);
+    assert!(max > 2.0 * min, "{levels:?}");
+}
+
+/// Rms of slot `slot` of `tag` over `samples` samples after a one second settle.
+fn rms(rack: &mut Rack, tag: Tag, slot: usize, samples: usize, sample_rate: f32) -> f32 {
+    for _ in 0..sample_rate as usize {
+        rack.play(sample_rate);
+    }
+    let mut e = 0.0;
+    for _ in 0..samples {
+        rack.play(sample_rate);
+        e += rack.outputs[(tag, slot)].powi(2);
+    }
+    (e / samples as f32).sqrt()
+}
+
+#[test]
+fn phaser_allpass() {
+    let sr = 44100.0;
+    let mut rack = Rack::default();
+    let sine = OscBuilder::new(sine_osc).hz(300.0).rack(&mut rack);
+    let phaser = PhaserBuilder::new(sine.tag())
+        .stages(12)
+        .rate(0.0)
+        .feedback(0.0)
+        .mix(1.0)
+        .rack(&mut rack);
+    let level = rms(&mut rack, phaser.tag(), 0, 44100, sr);
+    assert!((level - 0.5f32.sqrt()).abs() < 1e-3, "{level}");
+}
+
+#[test]
+fn phaser_notch() {
+    let sr = 44100.0;
+    let centre = 1000.0f32;
+    // With 4 stages the phase shift reaches 180 degrees where each stage
+    // shifts by 45.
+    let t = (PI * centre / sr).tan() * (PI / 8.0).tan();
+    let notch = t.atan() * sr / PI;
+    let mut rack = Rack::default();
+    let sine = OscBuilder::new(sine_osc).hz(notch).rack(&mut rack);
+    let phaser = PhaserBuilder::new(sine.tag())
+        .stages(4)
+        .depth(0.0)
+        .centre(centre)
+        .feedback(0.0)
+        .mix(0.5)
+        .rack(&mut rack);
+    let level = rms(&mut rack, phaser.tag(), 0, 44100, sr);
+    assert!(level < 0.01, "{level}");
+}
+
+#[test]
+fn phaser_external_modulation() {
+    let sr = 44100.0;
+    let mut rack = Rack::default();
+    let sine = OscBuilder::new(sine_osc).hz(440.0).rack(&mut rack);
+    let m = ConstBuilder::new(1.0.into()).rack(&mut rack);
+    let modulated = PhaserBuilder::new(sine.tag())
+        .centre(500.0)
+        .depth(1.0)
+        .modulation(m.tag())
+        .rack(&mut rack);
+    let fixed = PhaserBuilder::new(sine.tag())
+        .centre(1000.0)
+        .depth(0.0)
+        .rack(&mut rack);
+    assert!(modulated.modulated(&rack));
+    for _ in 0..1000 {
+        rack.play(sr);
+        for slot in 0..2 {
+            let a = rack.outputs[(modulated.tag(), slot)];
+            let b = rack.outputs[(fixed.tag(), slot)];
+            assert!((a - b).abs() < 1e-5);
+        }
+    }
+}
+
+#[test]
+fn phaser_stereo() {
+    let sr = 44100.0;
+    let mut rack = Rack::default();
+    let sine = OscBuilder::new(sine_osc).hz(440.0).rack(&mut rack);
+    let phaser = PhaserBuilder::new(sine.tag()).rate(2.0).rack(&mut rack);
+    let mut differ = false;
+    for _ in 0..4410 {
+        rack.play(sr);
+        let o = rack.outputs.outputs(phaser.tag());
+        differ |= (o[0] - o[1]).abs() > 1e-3;
+    }
+    assert!(differ);
+}
+
+#[test]
+#[should_panic]
+fn phaser_stages() {
+    let mut rack = Rack::default();
+    let sine = OscBuilder::new(sine_osc).rack(&mut rack);
+    PhaserBuilder::new(sine.tag()).stages(2).rack(&mut rack);
+}
+
+fn echo_impulse<F>(configure: F, rack: &mut Rack, samples: usize) -> Vec<[f32; 2]>
+where
+    F: FnOnce(&mut EchoDelayBuilder),
+{
+    let imp = ConstBuilder::new(1.0.into()).rack(rack);
+    let mut builder = EchoDelayBuilder::new(imp.tag());
+    configure(&mut builder);
+    let echo = builder.rack(rack);
+    let mut out = Vec::with_capacity(samples);
+    for _ in 0..samples {
+        rack.play(44100.0);
+        imp.set_value(rack, 0.0.into());
+        let o = rack.outputs.outputs(echo.tag());
+        out.push([o[0], o[1]]);
+    }
+    out
+}
+
+/// Index of the largest absolute value in `xs`.
+fn peak(xs: impl Iterator<Item = f32>) -> usize {
+    xs.enumerate()
+        .fold(
+            (0, 0.0),
+            |(i, m), (j, x)| if x.abs() > m { (j, x.abs()) } else { (i, m) },
+        )
+        .0
+}
+
+#[test]
+fn echo_repeats() {
+    let mut rack = Rack::default();
+    let out = echo_impulse(
+        |b| {
+            b.time(0.01).feedback(0.5).mix(1.0);
+        },
+        &mut rack,
+        1400,
+    );
+    assert!(out[..441].iter().all(|x| x[0] == 0.0 && x[1] == 0.0));
+    assert!((out[441][0] - 1.0).abs() < 1e-6);
+    assert!((out[441][1] - 1.0).abs() < 1e-6);
+    // The second repeat has been through the feedback gain and filters.
+    let second = peak(out[800..1000].iter().map(|x| x[0])) + 800;
+    assert!((second as i32 - 882).abs() <= 2, "{second}");
+    assert!(out[second][0].abs() < 0.5 && out[second][0].abs() > 0.2);
+}
+
+#[test]
+fn echo_feedback_is_bounded() {
+    let mut rack = Rack::default();
+    let out = echo_impulse(
+        |b| {
+            b.time(0.001).feedback(2.0).lowpass(20_000.0).highpass(1.0);
+        },
+        &mut rack,
+        44100,
+    );
+    assert!(out.iter().all(|x| x[0].abs() <= 1.0 && x[1].abs() <= 1.0));
+}
+
+#[test]
+fn echo_ping_pong() {
+    let mut rack = Rack::default();
+    let out = echo_impulse(
+        |b| {
+            b.time(0.01).feedback(0.9).ping_pong(true).mix(1.0);
+        },
+        &mut rack,
+        1400,
+    );
+    assert!((out[441][0] - 1.0).abs() < 1e-6);
+    assert_eq!(out[441][1], 0.0);
+    let right = peak(out[800..1000].iter().map(|x| x[1])) + 800;
+    assert!((right as i32 - 882).abs() <= 2, "{right}");
+    let left = out[800..1000].iter().map(|x| x[0].abs()).sum::<f32>();
+    assert_eq!(left, 0.0);
+    let third = peak(out[1200..1400].iter().map(|x| x[0])) + 1200;
+    assert!((third as i32 - 1323).abs() <= 3, "{third}");
+}
+
+#[test]
+fn echo_sync() {
+    let mut rack = Rack::default();
+    let clock = TempoClockBuilder::new(120.0).rack(&mut rack);
+    let imp = ConstBuilder::new(1.0.into()).rack(&mut rack);
+    let echo = EchoDelayBuilder::new(imp.tag())
+        .sync(clock.tag())
+        .division(0.5)
+        .mix(1.0)
+        .rack(&mut rack);
+    assert!(echo.synced(&rack));
+    assert_eq!(echo.delay_time(&rack), 0.25);
+    let mut first = None;
+    for i in 0..20000 {
+        rack.play(44100.0);
+        imp.set_value(&mut rack, 0.0.into());
+        if first.is_none() && rack.outputs[(echo.tag(), 0)] != 0.0 {
+            first = Some(i);
+        }
+    }
+    assert_eq!(first, Some(11025));
+    clock.set_bpm(&mut rack, 60.0.into());
+    rack.play(44100.0);
+    assert_eq!(echo.delay_time(&rack), 0.5);
+    echo.set_sync(&mut rack, None);
+    assert_eq!(echo.delay_time(&rack), echo.time(&rack));
+}
+
+#[test]
+fn echo_time_change_is_smooth() {
+    let sr = 44100.0;
+    let mut rack = Rack::default();
+    let sine = OscBuilder::new(sine_osc).hz(220.0).rack(&mut rack);
+    let echo = EchoDelayBuilder::new(sine.tag())
+        .time(0.1)
+        .feedback(0.0)
+        .rack(&mut rack);
+    let mut prev = 0.0;
+    let mut max_step = 0.0f32;
+    for i in 0..44100 {
+        if i == 22050 {
+            echo.set_time(&mut rack, 0.1234.into());
+        }
+        rack.play(sr);
+        let x = rack.outputs[(echo.tag(), 0)];
+        if i > 4410 {
+            max_step = max_step.max((x - prev).abs());
+        }
+        prev = x;
+    }
+    // A 220 Hz sine moves by at most 0.032 per sample, a jump between taps
+    // would be far larger.
+    assert!(max_step < 0.05, "{max_step}");
+}
+
+fn db(x: f32) -> f32 {
+    20.0 * x.abs().log10()
+}
+
+#[test]
+fn compressor_ratio() {
+    let mut rack = Rack::default();
+    let c = ConstBuilder::new(1.0.into()).rack(&mut rack);
+    let comp = CompressorBuilder::new(c.tag())
+        .threshold(-20.0)
+        .ratio(4.0)
+        .knee(0.0)
+        .rack(&mut rack);
+    for _ in 0..44100 {
+        rack.play(44100.0);
+    }
+    let out = rack.outputs.outputs(comp.tag());
+    assert!((db(out[0]) + 15.0).abs() < 1e-3, "{}", db(out[0]));
+    assert!((out[1] - 15.0).abs() < 1e-3);
+
+    comp.set_makeup(&mut rack, 6.0.into());
+    rack.play(44100.0);
+    assert!((db(rack.outputs[(comp.tag(), 0)]) + 9.0).abs() < 1e-3);
+}
+
+#[test]
+fn compressor_knee() {
+    let mut rack = Rack::default();
+    let c = ConstBuilder::new(0.1.into()).rack(&mut rack);
+    let comp = CompressorBuilder::new(c.tag())
+        .threshold(-20.0)
+        .ratio(4.0)
+        .knee(8.0)
+        .rack(&mut rack);
+    // At the threshold a soft knee already reduces by (1 - 1/ratio) * knee / 8.
+    assert!((comp.gain_db(&rack, -20.0) + 0.75).abs() < 1e-5);
+    assert_eq!(comp.gain_db(&rack, -24.0), 0.0);
+    assert!((comp.gain_db(&rack, -10.0) + 7.5).abs() < 1e-5);
+    for _ in 0..44100 {
+        rack.play(44100.0);
+    }
+    assert!((rack.outputs[(comp.tag(), 1)] - 0.75).abs() < 1e-3);
+}
+
+#[test]
+fn compressor_below_threshold() {
+    let mut rack = Rack::default();
+    let sine = OscBuilder::new(sine_osc).amplitude(0.05).rack(&mut rack);
+    let comp = CompressorBuilder::new(sine.tag())
+        .threshold(-20.0)
+        .knee(0.0)
+        .rack(&mut rack);
+    for _ in 0..4410 {
+        rack.play(44100.0);
+        let out = rack.outputs.outputs(comp.tag());
+        assert_eq!(out[0], rack.outputs[(sine.tag(), 0)]);
+        assert_eq!(out[1], 0.0);
+    }
+}
+
+#[test]
+fn compressor_attack_release() {
+    let sr = 44100.0;
+    let mut rack = Rack::default();
+    let c = ConstBuilder::new(1.0.into()).rack(&mut rack);
+    let comp = CompressorBuilder::new(c.tag())
+        .threshold(-20.0)
+        .ratio(4.0)
+        .knee(0.0)
+        .attack(0.01)
+        .release(0.1)
+        .rack(&mut rack);
+    // After one time constant the reduction is 63% of the way there.
+    for _ in 0..441 {
+        rack.play(sr);
+    }
+    let reduction = rack.outputs[(comp.tag(), 1)];
+    assert!(
+        (reduction - 15.0 * (1.0 - (-1.0f32).exp())).abs() < 0.1,
+        "{reduction}"
+    );
+    for _ in 0..44100 {
+        rack.play(sr);
+    }
+    c.set_value(&mut rack, 0.0.into());
+    for _ in 0..4410 {
+        rack.play(sr);
+    }
+    let reduction = rack.outputs[(comp.tag(), 1)];
+    assert!(
+        (reduction - 15.0 * (-1.0f32).exp()).abs() < 0.1,
+        "{reduction}"
+    );
+}
+
+#[test]
+fn compressor_sidechain() {
+    let mut rack = Rack::default();
+    let pad = ConstBuilder::new(0.1.into()).rack(&mut rack);
+    let kick = ConstBuilder::new(1.0.into()).rack(&mut rack);
+    let comp = CompressorBuilder::new(pad.tag())
+        .threshold(-20.0)
+        .ratio(4.0)
+        .knee(0.0)
+        .sidechain(kick.tag())
+        .rack(&mut rack);
+    assert!(comp.sidechained(&rack));
+    for _ in 0..44100 {
+        rack.play(44100.0);
+    }
+    // The pad is ducked by the kick's 15 dB of reduction.
+    assert!((db(rack.outputs[(comp.tag(), 0)]) + 35.0).abs() < 1e-3);
+
+    comp.set_sidechain(&mut rack, None);
+    for _ in 0..44100 {
+        rack.play(44100.0);
+    }
+    assert!((rack.outputs[(comp.tag(), 0)] - 0.1).abs() < 1e-5);
+}
+
+#[test]
+fn compressor_lookahead() {
+    let out = impulse_response(
+        |rack, input| {
+            CompressorBuilder::new(input)
+                .threshold(0.0)
+                .knee(0.0)
+                .lookahead(0.0001)
+                .rack(rack)
+                .tag()
+        },
+        8,
+        44100.0,
+    );
+    assert_eq!(out, vec![0.0, 0.0, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0]);
+}
+
+#[test]
+fn frequency_shifter() {
+    let sr = 44100.0;
+    let mut rack = Rack::default();
+    let sine = OscBuilder::new(sine_osc).hz(1000.0).rack(&mut rack);
+    let fs = FrequencyShifterBuilder::new(sine.tag())
+        .hz(100.0)
+        .rack(&mut rack);
+    for _ in 0..4410 {
+        rack.play(sr);
+    }
+    let (mut up, mut down) = (vec![], vec![]);
+    for _ in 0..44100 {
+        rack.play(sr);
+        up.push(rack.outputs[(fs.tag(), FrequencyShifter::UP)]);
+        down.push(rack.outputs[(fs.tag(), FrequencyShifter::DOWN)]);
+    }
+    assert!(dft(&up, 1100.0, sr) > 0.99, "{}", dft(&up, 1100.0, sr));
+    assert!(dft(&up, 900.0, sr) < 0.01);
+    assert!(dft(&up, 1000.0, sr) < 0.01);
+    assert!(dft(&down, 900.0, sr) > 0.99);
+    assert!(dft(&down, 1100.0, sr) < 0.01);
+}
+
+#[test]
+fn ring_mod() {
+    let sr = 44100.0;
+    let mut rack = Rack::default();
+    let carrier = OscBuilder::new(sine_osc).hz(1000.0).rack(&mut rack);
+    let modulator = OscBuilder::new(sine_osc).hz(300.0).rack(&mut rack);
+    let rm = RingModBuilder::new(carrier.tag(), modulator.tag()).rack(&mut rack);
+    let mut out = vec![];
+    for _ in 0..44100 {
+        rack.play(sr);
+        out.push(rack.outputs[(rm.tag(), 0)]);
+    }
+    assert!((dft(&out, 700.0, sr) - 0.5).abs() < 0.01);
+    assert!((dft(&out, 1300.0, sr) - 0.5).abs() < 0.01);
+    assert!(dft(&out, 1000.0, sr) < 0.01);
+
+    // Half mixed, the carrier comes through at half level.
+    rm.set_mix(&mut rack, 0.5.into());
+    out.clear();
+    for _ in 0..44100 {
+        rack.play(sr);
+        out.push(rack.outputs[(rm.tag(), 0)]);
+    }
+    assert!((dft(&out, 1000.0, sr) - 0.5).abs() < 0.01);
+    assert!((dft(&out, 700.0, sr) - 0.25).abs() < 0.01);
+}
+
+#[test]
+fn ring_mod_diode() {
+    let sr = 44100.0;
+    let mut rack = Rack::default();
+    let carrier = OscBuilder::new(sine_osc).hz(1000.0).rack(&mut rack);
+    let modulator = OscBuilder::new(sine_osc).hz(300.0).rack(&mut rack);
+    let rm = RingModBuilder::new(carrier.tag(), modulator.tag())
+        .diode(true)
+        .rack(&mut rack);
+    let mut out = vec![];
+    for _ in 0..44100 {
+        rack.play(sr);
+        out.push(rack.outputs[(rm.tag(), 0)]);
+    }
+    // The sidebands are still there, along with products of 3 x the carrier.
+    assert!(dft(&out, 700.0, sr) > 0.2);
+    assert!(dft(&out, 1300.0, sr) > 0.2);
+    assert!(dft(&out, 2700.0, sr) > 0.05);
+    assert!(dft(&out, 3300.0, sr) > 0.05);
+    // Silence on either input silences the output.
+    modulator.set_amplitude(&mut rack, 0.0.into());
+    rack.play(sr);
+    assert_eq!(rack.outputs[(rm.tag(), 0)], 0.0);
+}
+
+fn pitch_shift(semitones: f32, cents: f32) -> Vec<f32> {
+    let sr = 44100.0;
+    let mut rack = Rack::default();
+    let sine = OscBuilder::new(sine_osc).hz(440.0).rack(&mut rack);
+    let ps = PitchShiftBuilder::new(sine.tag())
+        .semitones(semitones)
+        .cents(cents)
+        .rack(&mut rack);
+    for _ in 0..4410 {
+        rack.play(sr);
+    }
+    (0..44100)
+        .map(|_| {
+            rack.play(sr);
+            rack.outputs[(ps.tag(), 0)]
+        })
+        .collect()
+}
+
+#[test]
+fn pitch_shift_octaves() {
+    let sr = 44100.0;
+    let up = pitch_shift(12.0, 0.0);
+    assert!(dft(&up, 880.0, sr) > 10.0 * dft(&up, 440.0, sr));
+    let down = pitch_shift(-12.0, 0.0);
+    assert!(dft(&down, 220.0, sr) > 10.0 * dft(&down, 440.0, sr));
+    let fifth = pitch_shift(7.0, 0.0);
+    let hz = 440.0 * 2f32.powf(7.0 / 12.0);
+    assert!(dft(&fifth, hz, sr) > 10.0 * dft(&fifth, 440.0, sr));
+}
+
+#[test]
+fn pitch_shift_cents() {
+    let mut rack = Rack::default();
+    let sine = OscBuilder::new(sine_osc).rack(&mut rack);
+    let ps = PitchShiftBuilder::new(sine.tag())
+        .semitones(-1.0)
+        .cents(1300.0)
+        .rack(&mut rack);
+    assert!((ps.ratio(&rack) - 2.0).abs() < 1e-6);
+    let a = pitch_shift(-1.0, 1300.0);
+    let b = pitch_shift(12.0, 0.0);
+    for (x, y) in a.iter().zip(b.iter()) {
+        assert!((x - y).abs() < 1e-4);
+    }
+}
+
+#[test]
+fn pitch_shift_unison() {
+    // With no shift the taps stand still and a sine passes at the same level.
+    let out = pitch_shift(0.0, 0.0);
+    assert!((dft(&out, 440.0, 44100.0) - 1.0).abs() < 0.05);
+}
@@ -0,0 +1,160 @@
+use oscen::envelopes::*;
+use oscen::oscillators::*;
+use oscen::rack::*;
+
+const SR: f32 = 1000.0;
+
+fn play(rack: &mut Rack, adsr: &Adsr, samples: usize) -> Vec<f32> {
+    (0..samples)
+        .map(|_| {
+            rack.play(SR);
+            rack.outputs[(adsr.tag(), 0)]
+        })
+        .collect()
+}
+
+#[test]
+fn adsr_gate() {
+    let mut rack = Rack::default();
+    let gate = ConstBuilder::new(0.0.into()).rack(&mut rack);
+    let adsr = AdsrBuilder::linear()
+        .attack(0.1)
+        .decay(0.1)
+        .sustain(0.5)
+        .release(0.1)
+        .gate(Control::V(gate.tag(), 0))
+        .rack(&mut rack);
+    gate.set_value(&mut rack, 1.0.into());
+    let out = play(&mut rack, &adsr, 300);
+    assert!(adsr.triggered(&rack));
+    assert!(out[50] > 0.4 && out[50] < 0.6);
+    assert!((out[99] - 1.0).abs() < 0.02);
+    assert_eq!(out[299], 0.5);
+
+    gate.set_value(&mut rack, 0.0.into());
+    let out = play(&mut rack, &adsr, 200);
+    assert!(!adsr.triggered(&rack));
+    assert!(out[0] <= 0.5 && out[0] > 0.4);
+    assert_eq!(out[199], 0.0);
+}
+
+/// An envelope with a 100 ms linear attack, 100 ms decay to 0.5 and 100 ms
+/// release, gated by the returned `Const`.
+fn gated_adsr(rack: &mut Rack) -> (std::sync::Arc<Const>, std::sync::Arc<Adsr>) {
+    let gate = ConstBuilder::new(0.0.into()).rack(rack);
+    let adsr = AdsrBuilder::linear()
+        .attack(0.1)
+        .decay(0.1)
+        .sustain(0.5)
+        .release(0.1)
+        .gate(Control::V(gate.tag(), 0))
+        .rack(rack);
+    (gate, adsr)
+}
+
+fn releases_from(out: &[f32], level: f32) {
+    assert!(
+        out[0] <= level && out[0] > level - 0.05,
+        "{} {level}",
+        out[0]
+    );
+    assert!(out.windows(2).all(|w| w[1] <= w[0]));
+    assert_eq!(out[110], 0.0);
+}
+
+#[test]
+fn adsr_silent_until_triggered() {
+    let mut rack = Rack::default();
+    let (_, adsr) = gated_adsr(&mut rack);
+    assert!(play(&mut rack, &adsr, 100).iter().all(|x| *x == 0.0));
+}
+
+#[test]
+fn adsr_gate_falls_during_attack() {
+    let mut rack = Rack::default();
+    let (gate, adsr) = gated_adsr(&mut rack);
+    gate.set_value(&mut rack, 1.0.into());
+    let level = *play(&mut rack, &adsr, 20).last().unwrap();
+    assert!(level > 0.15 && level < 0.25);
+    gate.set_value(&mut rack, 0.0.into());
+    releases_from(&play(&mut rack, &adsr, 120), level);
+}
+
+#[test]
+fn adsr_gate_falls_during_decay() {
+    let mut rack = Rack::default();
+    let (gate, adsr) = gated_adsr(&mut rack);
+    gate.set_value(&mut rack, 1.0.into());
+    let level = *play(&mut rack, &adsr, 150).last().unwrap();
+    assert!(level > 0.7 && level < 0.8);
+    gate.set_value(&mut rack, 0.0.into());
+    releases_from(&play(&mut rack, &adsr, 120), level);
+}
+
+#[test]
+fn adsr_gate_rises_during_release() {
+    let mut rack = Rack::default();
+    let (gate, adsr) = gated_adsr(&mut rack);
+    gate.set_value(&mut rack, 1.0.into());
+    play(&mut rack, &adsr, 300);
+    gate.set_value(&mut rack, 0.0.into());
+    let level = *play(&mut rack, &adsr, 50).last().unwrap();
+    assert!(level > 0.2 && level < 0.3);
+    gate.set_value(&mut rack, 1.0.into());
+    let out = play(&mut rack, &adsr, 100);
+    // The attack picks up from the release level rather than from zero.
+    assert!(
+        out[0] >= level && out[0] < level + 0.05,
+        "{} {level}",
+        out[0]
+    );
+    assert!(out[..60].windows(2).all(|w| w[1] >= w[0]));
+}
+
+#[test]
+fn adsr_gate_matches_on_off() {
+    let mut rack = Rack::default();
+    let gate = ConstBuilder::new(0.0.into()).rack(&mut rack);
+    let build = |rack: &mut Rack, g: Control| {
+        AdsrBuilder::exp_20()
+            .attack(0.05)
+            .decay(0.05)
+            .sustain(0.3)
+            .release(0.05)
+            .gate(g)
+            .rack(rack)
+    };
+    let gated = build(&mut rack, Control::V(gate.tag(), 0));
+    let manual = build(&mut rack, 0.0.into());
+    for (i, g) in [1.0, 0.0, 1.0, 0.0].iter().enumerate() {
+        gate.set_value(&mut rack, (*g).into());
+        if *g > 0.0 {
+            manual.on(&mut rack);
+        } else {
+            manual.off(&mut rack);
+        }
+        for _ in 0..(40 + 30 * i) {
+            rack.play(SR);
+            assert_eq!(
+                rack.outputs[(gated.tag(), 0)],
+                rack.outputs[(manual.tag(), 0)]
+            );
+        }
+    }
+}
+
+#[test]
+fn adsr_gate_from_oscillator() {
+    // A square wave gate retriggers the envelope on every cycle.
+    let mut rack = Rack::default();
+    let square = OscBuilder::new(square_osc).hz(5.0).rack(&mut rack);
+    let adsr = AdsrBuilder::linear()
+        .attack(0.02)
+        .sustain(1.0)
+        .release(0.02)
+        .gate(Control::V(square.tag(), 0))
+        .rack(&mut rack);
+    let out = play(&mut rack, &adsr, 1000);
+    let attacks = out.windows(2).filter(|w| w[0] == 0.0 && w[1] > 0.0).count();
+    assert_eq!(attacks, 5);
+}
@@ -0,0 +1,437 @@
+mod common;
+
+use common::dft;
+use oscen::filters::*;
+use oscen::operators::MixerBuilder;
+use oscen::oscillators::*;
+use oscen::rack::*;
+use oscen::utils::{frequency_response, impulse_response, log_frequencies};
+
+#[test]
+fn svf_dc() {
+    let mut rack = Rack::default();
+    let c = ConstBuilder::new(1.0.into()).rack(&mut rack);
+    let svf = SvfBuilder::new(c.tag()).cut_off(1000.0).rack(&mut rack);
+    for _ in 0..4410 {
+        rack.mono(44100.0);
+    }
+    let out = rack.outputs.outputs(svf.tag());
+    assert!((out[Svf::LOWPASS] - 1.0).abs() < 1e-4);
+    assert!(out[Svf::HIGHPASS].abs() < 1e-4);
+    assert!(out[Svf::BANDPASS].abs() < 1e-4);
+    assert!((out[Svf::NOTCH] - 1.0).abs() < 1e-4);
+}
+
+#[test]
+fn svf_audio_rate_modulation() {
+    let mut rack = Rack::default();
+    let noise = WhiteNoiseBuilder::new().seed(0).rack(&mut rack);
+    let lfo = OscBuilder::new(sine_osc)
+        .hz(3000.0)
+        .amplitude(9000.0)
+        .rack(&mut rack);
+    let offset = ConstBuilder::new(10_000.0.into()).rack(&mut rack);
+    let cutoff = MixerBuilder::new(vec![lfo.tag(), offset.tag()]).rack(&mut rack);
+    SvfBuilder::new(noise.tag())
+        .cut_off(cutoff.tag())
+        .q(20.0)
+        .rack(&mut rack);
+    for _ in 0..44100 {
+        let out = rack.play(44100.0);
+        assert!(out[0..5].iter().all(|x| x.is_finite() && x.abs() < 1e3));
+    }
+}
+
+#[test]
+fn ladder_passband_gain() {
+    for poles in [2, 4] {
+        let mut rack = Rack::default();
+        let c = ConstBuilder::new(0.1.into()).rack(&mut rack);
+        LadderBuilder::new(c.tag())
+            .cut_off(1000.0)
+            .resonance(0.5)
+            .poles(poles)
+            .rack(&mut rack);
+        let mut out = 0.0;
+        for _ in 0..44100 {
+            out = rack.mono(44100.0);
+        }
+        assert!((out - 0.1).abs() < 1e-3, "{poles} poles: got {out}");
+    }
+}
+
+#[test]
+fn ladder_stability() {
+    let sample_rate = 44100.0;
+    let mut cutoff = 20.0;
+    while cutoff < 0.5 * sample_rate {
+        let mut rack = Rack::default();
+        let noise = WhiteNoiseBuilder::new().seed(1).rack(&mut rack);
+        LadderBuilder::new(noise.tag())
+            .cut_off(cutoff)
+            .resonance(1.2)
+            .drive(4.0)
+            .rack(&mut rack);
+        for _ in 0..4410 {
+            let out = rack.mono(sample_rate);
+            assert!(
+                out.is_finite() && out.abs() < 10.0,
+                "cutoff {cutoff}: {out}"
+            );
+        }
+        cutoff *= 1.5;
+    }
+}
+
+#[test]
+fn ladder_self_oscillation() {
+    let mut rack = Rack::default();
+    let c = ConstBuilder::new(0.01.into()).rack(&mut rack);
+    LadderBuilder::new(c.tag())
+        .cut_off(1000.0)
+        .resonance(1.1)
+        .rack(&mut rack);
+    rack.mono(44100.0);
+    c.set_value(&mut rack, 0.0.into());
+    let peak = (0..44100)
+        .map(|_| rack.mono(44100.0).abs())
+        .skip(22050)
+        .fold(0.0, f32::max);
+    assert!(peak > 0.1, "peak {peak}");
+}
+
+#[test]
+fn biquad_response() {
+    let sr = 44100.0;
+    let db = |kind, hz, gain| {
+        let c = BiquadCoefficients::new(kind, 1000.0, 0.707, gain, sr);
+        20.0 * c.response(hz, sr).0.log10()
+    };
+    assert!((db(BiquadType::Lowpass, 1000.0, 0.0) + 3.0).abs() < 0.05);
+    assert!((db(BiquadType::Highpass, 1000.0, 0.0) + 3.0).abs() < 0.05);
+    assert!(db(BiquadType::Notch, 1000.0, 0.0) < -60.0);
+    assert!((db(BiquadType::Bandpass, 1000.0, 0.0)).abs() < 0.05);
+    assert!((db(BiquadType::Peaking, 1000.0, 6.0) - 6.0).abs() < 0.05);
+    assert!((db(BiquadType::LowShelf, 10.0, -9.0) + 9.0).abs() < 0.05);
+    assert!((db(BiquadType::HighShelf, 20_000.0, 4.0) - 4.0).abs() < 0.05);
+    for hz in [20.0, 500.0, 5000.0, 18_000.0] {
+        assert!(db(BiquadType::Allpass, hz, 0.0).abs() < 0.01);
+    }
+}
+
+#[test]
+fn biquad_switch_type() {
+    fn run(switch: bool) -> Vec<f32> {
+        let mut rack = Rack::default();
+        let sine = OscBuilder::new(sine_osc).hz(1000.0).rack(&mut rack);
+        let biquad = BiquadBuilder::new(sine.tag(), BiquadType::Lowpass).rack(&mut rack);
+        (0..4410)
+            .map(|i| {
+                if switch && i == 2205 {
+                    biquad.set_kind(&mut rack, BiquadType::Highpass);
+                }
+                rack.mono(44100.0)
+            })
+            .collect()
+    }
+    let (lowpass, switched) = (run(false), run(true));
+    // The highpass is faded in, so at first the output barely leaves the lowpass.
+    assert_eq!(lowpass[2205], switched[2205]);
+    for i in 2205..2215 {
+        assert!(
+            (lowpass[i] - switched[i]).abs() < 0.05,
+            "jump at sample {i}"
+        );
+    }
+    assert!((lowpass[4409] - switched[4409]).abs() > 0.5);
+}
+
+#[test]
+fn equalizer() {
+    let sr = 44100.0;
+    let mut rack = Rack::default();
+    let c = ConstBuilder::new(1.0.into()).rack(&mut rack);
+    let eq = EqualizerBuilder::new(c.tag())
+        .band(BiquadType::LowShelf, 100.0, 6.0, 0.707)
+        .band(BiquadType::Peaking, 1000.0, -3.0, 1.0)
+        .band(BiquadType::Lowpass, 15_000.0, 0.0, 0.707)
+        .rack(&mut rack);
+    assert!((eq.magnitude(&rack, 20.0, sr) - 6.0).abs() < 0.1);
+    assert!((eq.magnitude(&rack, 1000.0, sr) + 3.0).abs() < 0.1);
+    let curve = eq.magnitude_response(&rack, sr, 64);
+    assert_eq!(curve.len(), 64);
+    assert!((curve[0].0 - 20.0).abs() < 1e-3 && (curve[63].0 - 20_000.0).abs() < 1.0);
+    let mut out = 0.0;
+    for _ in 0..44100 {
+        out = rack.mono(sr);
+    }
+    assert!((20.0 * out.log10() - 6.0).abs() < 0.01);
+    eq.set_gain(&mut rack, 0, 0.0.into());
+    assert!(eq.magnitude(&rack, 20.0, sr).abs() < 0.1);
+}
+
+#[test]
+#[should_panic(expected = "Max number of equalizer bands")]
+fn equalizer_too_many_bands() {
+    let mut rack = Rack::default();
+    let c = ConstBuilder::new(1.0.into()).rack(&mut rack);
+    let mut eq = EqualizerBuilder::new(c.tag());
+    for _ in 0..11 {
+        eq.band(BiquadType::Peaking, 1000.0, 0.0, 1.0);
+    }
+    eq.rack(&mut rack);
+}
+
+#[test]
+fn cached_coefficients_follow_controls() {
+    let mut rack = Rack::default();
+    let sine = OscBuilder::new(sine_osc).hz(1000.0).rack(&mut rack);
+    let lpf = LpfBuilder::new(sine.tag()).cut_off(100.0).rack(&mut rack);
+    let peak = |rack: &mut Rack| {
+        (0..4410)
+            .map(|_| rack.mono(44100.0).abs())
+            .skip(2205)
+            .fold(0.0, f32::max)
+    };
+    assert!(peak(&mut rack) < 0.05);
+    lpf.set_cutoff(&mut rack, 10_000.0.into());
+    assert!(peak(&mut rack) > 0.95);
+}
+
+#[test]
+fn formant_vowels() {
+    let mut rack = Rack::default();
+    let sine = OscBuilder::new(sine_osc).hz(0.0).rack(&mut rack);
+    let formant = FormantBuilder::new(sine.tag())
+        .voice(VoiceType::Bass)
+        .rack(&mut rack);
+    let f = formant.formants(&rack);
+    assert_eq!((f[0].0, f[0].1, f[1].0), (600.0, 1.0, 1040.0));
+    formant.set_vowel(&mut rack, 0.5.into());
+    let f = formant.formants(&rack);
+    assert_eq!((f[0].0, f[1].0), (500.0, 1330.0));
+    // A sine at the first formant of the vowel I passes, one at the first
+    // formant of A is attenuated.
+    formant.set_vowel(&mut rack, 2.0.into());
+    let peak = |rack: &mut Rack, hz: f32| {
+        sine.set_hz(rack, hz.into());
+        (0..8820)
+            .map(|_| rack.mono(44100.0).abs())
+            .skip(4410)
+            .fold(0.0, f32::max)
+    };
+    assert!(peak(&mut rack, 250.0) > 0.9);
+    assert!(peak(&mut rack, 600.0) < 0.3);
+}
+
+#[test]
+fn lpf_frequency_response() {
+    let sr = 44100.0;
+    let response = frequency_response(
+        |rack, input| LpfBuilder::new(input).cut_off(1000.0).rack(rack).tag(),
+        &[100.0, 1000.0, 10_000.0],
+        sr,
+    );
+    assert!(response[0].1.abs() < 0.1);
+    assert!((response[1].1 + 3.0).abs() < 0.1, "{response:?}");
+    assert!(response[2].1 < -35.0);
+    assert!((response[1].2 + std::f32::consts::FRAC_PI_2).abs() < 0.05);
+}
+
+#[test]
+fn measured_matches_analytic() {
+    let sr = 44100.0;
+    let frequencies = log_frequencies(20.0, 20_000.0, 16);
+    let measured = frequency_response(
+        |rack, input| {
+            BiquadBuilder::new(input, BiquadType::Peaking)
+                .gain(6.0)
+                .rack(rack)
+                .tag()
+        },
+        &frequencies,
+        sr,
+    );
+    let c = BiquadCoefficients::new(BiquadType::Peaking, 1000.0, 0.707, 6.0, sr);
+    for (hz, db, phase) in measured {
+        let (mag, p) = c.response(hz, sr);
+        assert!((db - 20.0 * mag.log10()).abs() < 0.01);
+        assert!((phase - p).abs() < 0.01);
+    }
+}
+
+#[test]
+fn one_pole() {
+    let sr = 44100.0;
+    let db = |slot: usize, hz: f32| {
+        let response = frequency_response(
+            |rack, input| {
+                let one_pole = OnePoleBuilder::new(input).cut_off(500.0).rack(rack);
+                // Copy the output in `slot` to a module's first output.
+                ConstBuilder::new(Control::V(one_pole.tag(), slot))
+                    .rack(rack)
+                    .tag()
+            },
+            &[hz],
+            sr,
+        );
+        response[0].1
+    };
+    assert!((db(OnePole::LOWPASS, 500.0) + 3.0).abs() < 0.05);
+    assert!((db(OnePole::HIGHPASS, 500.0) + 3.0).abs() < 0.05);
+    assert!((db(OnePole::LOWPASS, 5000.0) + 20.0).abs() < 1.0);
+}
+
+#[test]
+fn dc_blocker() {
+    let mut rack = Rack::default();
+    let sine = OscBuilder::new(sine_osc).hz(440.0).rack(&mut rack);
+    let offset = ConstBuilder::new(0.5.into()).rack(&mut rack);
+    let input = MixerBuilder::new(vec![sine.tag(), offset.tag()]).rack(&mut rack);
+    DcBlockerBuilder::new(input.tag()).rack(&mut rack);
+    let out: Vec<f32> = (0..88200).map(|_| rack.mono(44100.0)).collect();
+    let mean = out[44100..].iter().sum::<f32>() / 44100.0;
+    assert!(mean.abs() < 1e-3, "mean {mean}");
+}
+
+#[test]
+fn slew() {
+    let mut rack = Rack::default();
+    let step = ConstBuilder::new(1.0.into()).rack(&mut rack);
+    SlewBuilder::new(step.tag())
+        .rise(0.1)
+        .fall(0.01)
+        .rack(&mut rack);
+    let rise: Vec<f32> = (0..100).map(|_| rack.mono(1000.0)).collect();
+    assert!(rise[98] < 0.99 && rise[99] >= 0.99);
+    assert!(rise.windows(2).all(|w| w[1] > w[0]));
+    step.set_value(&mut rack, 0.0.into());
+    let fall: Vec<f32> = (0..10).map(|_| rack.mono(1000.0)).collect();
+    assert!(fall[9] < 0.011);
+}
+
+#[test]
+fn comb_feedforward() {
+    let sr = 1000.0;
+    let h = impulse_response(
+        |rack, input| {
+            CombBuilder::new(input)
+                .delay(0.005)
+                .feedback(0.5)
+                .dampening(0.0)
+                .dampening_inverse(1.0)
+                .feedforward(true)
+                .rack(rack)
+                .tag()
+        },
+        8,
+        sr,
+    );
+    assert_eq!(h, vec![1.0, 0.0, 0.0, 0.0, 0.0, 0.5, 0.0, 0.0]);
+}
+
+#[test]
+fn comb_feedback_tuned() {
+    let sr = 1000.0;
+    let h = impulse_response(
+        |rack, input| {
+            CombBuilder::new(input)
+                .hz(250.0)
+                .feedback(0.5)
+                .dampening(0.0)
+                .dampening_inverse(1.0)
+                .rack(rack)
+                .tag()
+        },
+        13,
+        sr,
+    );
+    let expected = [
+        0.0, 0.0, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0, 0.5, 0.0, 0.0, 0.0, 0.25,
+    ];
+    for (x, e) in h.iter().zip(expected.iter()) {
+        assert!((x - e).abs() < 1e-6, "{h:?}");
+    }
+}
+
+#[test]
+fn comb_fractional_delay() {
+    let sr = 1000.0;
+    let h = impulse_response(
+        |rack, input| {
+            CombBuilder::new(input)
+                .delay(0.0045)
+                .feedback(0.0)
+                .rack(rack)
+                .tag()
+        },
+        8,
+        sr,
+    );
+    assert!(h[4] > 0.1 && h[5] > 0.1, "{h:?}");
+    assert!((h.iter().sum::<f32>() - 1.0).abs() < 1e-6);
+}
+
+fn run(rack: &mut Rack, tag: Tag, samples: usize) -> Vec<f32> {
+    (0..samples)
+        .map(|_| {
+            rack.play(44100.0);
+            rack.outputs[(tag, 0)]
+        })
+        .collect()
+}
+
+#[test]
+fn vocoder_follows_modulator_spectrum() {
+    let mut rack = Rack::default();
+    let carrier = OscBuilder::new(saw_osc).hz(100.0).rack(&mut rack);
+    let modulator = OscBuilder::new(sine_osc).hz(1000.0).rack(&mut rack);
+    let vocoder = VocoderBuilder::new(modulator.tag(), carrier.tag())
+        .unvoiced(0.0)
+        .rack(&mut rack);
+    run(&mut rack, vocoder.tag(), 4410);
+    let out = run(&mut rack, vocoder.tag(), 44100);
+    // Only the carrier harmonics near the modulator's frequency get through.
+    let near = dft(&out, 1000.0, 44100.0);
+    assert!(near > 10.0 * dft(&out, 300.0, 44100.0));
+    assert!(near > 10.0 * dft(&out, 4000.0, 44100.0));
+
+    // A silent modulator silences the output.
+    modulator.set_amplitude(&mut rack, 0.0.into());
+    run(&mut rack, vocoder.tag(), 44100);
+    let out = run(&mut rack, vocoder.tag(), 100);
+    assert!(out.iter().all(|x| x.abs() < 1e-4));
+}
+
+#[test]
+fn vocoder_unvoiced() {
+    let mut rack = Rack::default();
+    let carrier = ConstBuilder::new(0.0.into()).rack(&mut rack);
+    let modulator = OscBuilder::new(sine_osc).hz(15000.0).rack(&mut rack);
+    let vocoder = VocoderBuilder::new(modulator.tag(), carrier.tag())
+        .high(4000.0)
+        .unvoiced(0.5)
+        .rack(&mut rack);
+    run(&mut rack, vocoder.tag(), 4410);
+    let out = run(&mut rack, vocoder.tag(), 44100);
+    assert!((dft(&out, 15000.0, 44100.0) - 0.5).abs() < 0.02);
+}
+
+#[test]
+fn vocoder_bands() {
+    let mut rack = Rack::default();
+    let c = ConstBuilder::new(0.0.into()).rack(&mut rack);
+    let vocoder = VocoderBuilder::new(c.tag(), c.tag())
+        .bands(4)
+        .low(100.0)
+        .high(800.0)
+        .rack(&mut rack);
+    let bands = vocoder.band_frequencies(&rack);
+    let hz: Vec<f32> = bands.iter().map(|b| b.0).collect();
+    for (a, b) in hz.iter().zip([100.0, 200.0, 400.0, 800.0].iter()) {
+        assert!((a - b).abs() < 1e-2, "{hz:?}");
+    }
+    assert!((bands[0].1 - 2f32.sqrt()).abs() < 1e-4);
+    vocoder.set_bands(&mut rack, 100);
+    assert_eq!(vocoder.bands(&rack), VOCODER_MAX_BANDS);
+}
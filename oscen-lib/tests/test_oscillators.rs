@@ -31,3 +31,344 @@ fn clock() {
     let r4 = rack.mono(1f32);
     assert_eq!((r1, r2, r3, r4), (1.0, 0.0, 0.0, 1.0));
 }
+
+#[test]
+fn fourier_many_partials() {
+    let mut rack = Rack::default();
+    let osc = FourierOscBuilder::new(vec![1.0; 200])
+        .hz(1.0)
+        .lanczos(false)
+        .rack(&mut rack);
+    assert_eq!(osc.num_partials(&rack), 200);
+    rack.mono(1000f32);
+    let r = rack.mono(1000f32);
+    // Only partials below 500 Hz contribute.
+    let expected: f32 = (1..200)
+        .filter(|i| *i < 500)
+        .map(|i| (i as f32 * std::f32::consts::TAU / 1000.0).sin())
+        .sum();
+    assert!((r - expected).abs() < 1e-3, "got {r}, expected {expected}");
+}
+
+#[test]
+fn fourier_partials() {
+    let mut rack = Rack::default();
+    let osc = FourierOscBuilder::from_partials(vec![Partial::new(1.0, 2.5, 0.25)])
+        .lanczos(false)
+        .rack(&mut rack);
+    let r1 = rack.mono(1f32);
+    osc.set_partials(
+        &mut rack,
+        &[Partial::new(0.5, 1.0, 0.0), Partial::harmonic(2, 1.0)],
+    );
+    let r2 = rack.mono(1f32);
+    assert_eq!(osc.num_partials(&rack), 2);
+    assert!((r1 - 1.0).abs() < 1e-6);
+    assert!(r2.abs() < 1e-6);
+}
+
+#[test]
+fn fourier_lanczos_follows_ratio() {
+    // A lone partial is tapered by its ratio, not its position in the list.
+    let run = |partials: Vec<Partial>| {
+        let mut rack = Rack::default();
+        FourierOscBuilder::from_partials(partials).rack(&mut rack);
+        rack.mono(1000f32)
+    };
+    let sparse = run(vec![
+        Partial::new(0.0, 1.0, 0.0),
+        Partial::new(1.0, 3.0, 0.25),
+    ]);
+    let swapped = run(vec![
+        Partial::new(1.0, 3.0, 0.25),
+        Partial::new(0.0, 1.0, 0.0),
+    ]);
+    assert_eq!(sparse, swapped);
+    let expected = (std::f32::consts::PI * 0.75).sin() / (std::f32::consts::PI * 0.75);
+    assert!(
+        (sparse - expected).abs() < 1e-6,
+        "got {sparse}, expected {expected}"
+    );
+}
+
+#[test]
+fn fourier_nyquist() {
+    let mut rack = Rack::default();
+    FourierOscBuilder::from_partials(vec![
+        Partial::new(1.0, 1.0, 0.25),
+        Partial::new(1.0, 3.0, 0.25),
+    ])
+    .hz(200.0)
+    .lanczos(false)
+    .rack(&mut rack);
+    let r = rack.mono(1000f32);
+    assert!((r - 1.0).abs() < 1e-6, "got {r}, expected 1.0");
+}
+
+#[test]
+fn unison_mono() {
+    let mut rack = Rack::default();
+    UnisonOscBuilder::new(|x, _| x, 1)
+        .random_phase(false)
+        .hz(0.25)
+        .rack(&mut rack);
+    let r1 = rack.play(1f32);
+    let r2 = rack.play(1f32);
+    assert!((r1[0], r1[1]) == (0.0, 0.0));
+    assert!((r2[0] - 0.25).abs() < 1e-6 && (r2[1] - 0.25).abs() < 1e-6);
+}
+
+#[test]
+fn unison_spread() {
+    let mut rack = Rack::default();
+    let osc = UnisonOscBuilder::new(|_, _| 1.0, 3)
+        .detune(0.0)
+        .spread(1.0)
+        .mix(1.0)
+        .rack(&mut rack);
+    let r = rack.play(1f32);
+    assert!((r[0] - 1.0).abs() < 1e-6 && (r[1] - 1.0).abs() < 1e-6);
+    osc.set_mix(&mut rack, 0.0.into());
+    let r = rack.play(1f32);
+    assert!((r[0] - 1.0).abs() < 1e-6 && (r[1] - 1.0).abs() < 1e-6);
+}
+
+#[test]
+fn unison_flat_curve() {
+    let mut rack = Rack::default();
+    UnisonOscBuilder::new(|x, _| x, 3)
+        .random_phase(false)
+        .hz(0.25)
+        .detune(100.0)
+        .curve(0.0)
+        .mix(0.0)
+        .rack(&mut rack);
+    rack.play(1f32);
+    let r = rack.play(1f32);
+    assert!((r[0] - 0.25).abs() < 1e-6 && (r[1] - 0.25).abs() < 1e-6);
+}
+
+#[test]
+fn seeded_noise() {
+    fn run(seed: u64) -> Vec<f32> {
+        let mut rack = Rack::default();
+        let white = WhiteNoiseBuilder::new().seed(seed).rack(&mut rack);
+        let brown = ColoredNoiseBuilder::new(NoiseColor::Brown)
+            .seed(seed)
+            .rack(&mut rack);
+        let blue = ColoredNoiseBuilder::new(NoiseColor::Blue)
+            .seed(seed)
+            .rack(&mut rack);
+        let violet = ColoredNoiseBuilder::new(NoiseColor::Violet)
+            .seed(seed)
+            .rack(&mut rack);
+        let tags = [white.tag(), brown.tag(), blue.tag(), violet.tag()];
+        let mut out = vec![];
+        for _ in 0..64 {
+            rack.mono(44100f32);
+            out.extend(tags.iter().map(|t| rack.outputs[(*t, 0)]));
+        }
+        out
+    }
+    assert_eq!(run(7), run(7));
+    assert_ne!(run(7), run(8));
+}
+
+#[test]
+fn brown_noise_level() {
+    fn rms(sample_rate: f32) -> f32 {
+        let mut rack = Rack::default();
+        ColoredNoiseBuilder::new(NoiseColor::Brown)
+            .seed(11)
+            .rack(&mut rack);
+        let n = (4.0 * sample_rate) as usize;
+        let sum: f32 = (0..n).map(|_| rack.mono(sample_rate).powi(2)).sum();
+        (sum / n as f32).sqrt()
+    }
+    let (low, high) = (rms(44100.0), rms(96000.0));
+    assert!((low - 0.2).abs() < 0.04, "rms {low} at 44.1 kHz");
+    assert!((high - 0.2).abs() < 0.04, "rms {high} at 96 kHz");
+}
+
+#[test]
+fn colored_noise_set_color() {
+    let mut rack = Rack::default();
+    let noise = ColoredNoiseBuilder::new(NoiseColor::Brown)
+        .seed(2)
+        .rack(&mut rack);
+    for _ in 0..100 {
+        rack.mono(44100f32);
+    }
+    noise.set_color(&mut rack, NoiseColor::Violet);
+    assert!(rack.state.state(noise.tag()).iter().all(|x| *x == 0.0));
+    noise.set_bipolar(&mut rack, true);
+    assert!(noise.bipolar(&rack));
+}
+
+#[test]
+fn velvet_noise() {
+    let mut rack = Rack::default();
+    ColoredNoiseBuilder::new(NoiseColor::Velvet)
+        .density(100.0)
+        .seed(1)
+        .rack(&mut rack);
+    let impulses: Vec<f32> = (0..1000)
+        .map(|_| rack.mono(1000f32))
+        .filter(|x| *x != 0.0)
+        .collect();
+    assert_eq!(impulses.len(), 100);
+    assert!(impulses.iter().all(|x| x.abs() == 1.0));
+}
+
+#[test]
+fn dust() {
+    let mut rack = Rack::default();
+    ColoredNoiseBuilder::new(NoiseColor::Dust)
+        .density(0.0)
+        .rack(&mut rack);
+    assert!((0..100).all(|_| rack.mono(44100f32) == 0.0));
+    let mut rack = Rack::default();
+    ColoredNoiseBuilder::new(NoiseColor::Dust)
+        .density(1000.0)
+        .seed(3)
+        .rack(&mut rack);
+    let out: Vec<f32> = (0..1000).map(|_| rack.mono(1000f32)).collect();
+    assert!(out.iter().all(|x| *x >= 0.0 && *x <= 1.0));
+    assert!(out.iter().any(|x| *x > 0.0));
+}
+
+#[test]
+fn tempo_clock() {
+    let mut rack = Rack::default();
+    // 60 bpm at a sample rate of 16 gives 16 samples per beat.
+    let clock = TempoClockBuilder::new(60.0).rack(&mut rack);
+    let mut counts = [0; 5];
+    for _ in 0..32 {
+        let out = rack.play(16f32);
+        for (c, o) in counts.iter_mut().zip(out.iter()) {
+            *c += *o as usize;
+        }
+    }
+    assert_eq!(counts, [2, 4, 8, 6, 12]);
+    assert_eq!(rack.outputs[(clock.tag(), TempoClock::BEAT)], 31.0 / 16.0);
+    assert_eq!(rack.outputs[(clock.tag(), TempoClock::BAR)], 31.0 / 64.0);
+    assert_eq!(rack.outputs[(clock.tag(), TempoClock::BPM)], 60.0);
+}
+
+#[test]
+fn tempo_clock_empty_bar() {
+    let mut rack = Rack::default();
+    let clock = TempoClockBuilder::new(60.0)
+        .beats_per_bar(0.0)
+        .rack(&mut rack);
+    for _ in 0..8 {
+        rack.play(16f32);
+    }
+    assert_eq!(rack.outputs[(clock.tag(), TempoClock::BAR)], 7.0 / 16.0);
+}
+
+#[test]
+fn tempo_clock_swing() {
+    let mut rack = Rack::default();
+    TempoClockBuilder::new(60.0).swing(1.0).rack(&mut rack);
+    let eighths: Vec<usize> = (0..16)
+        .filter(|_| rack.play(16f32)[TempoClock::EIGHTH] == 1.0)
+        .collect();
+    assert_eq!(eighths, vec![0, 12]);
+}
+
+#[test]
+fn tempo_clock_stop_reset() {
+    let mut rack = Rack::default();
+    let clock = TempoClockBuilder::new(60.0).rack(&mut rack);
+    for _ in 0..5 {
+        rack.play(16f32);
+    }
+    clock.stop(&mut rack);
+    let stopped = rack.play(16f32);
+    assert_eq!(stopped[TempoClock::BEAT], 5.0 / 16.0);
+    assert_eq!(rack.play(16f32)[TempoClock::BEAT], 5.0 / 16.0);
+    clock.start(&mut rack);
+    clock.set_reset_input(&mut rack, 1.0.into());
+    let out = rack.play(16f32);
+    assert_eq!(
+        (out[TempoClock::QUARTER], out[TempoClock::BEAT]),
+        (1.0, 0.0)
+    );
+}
+
+#[test]
+fn lfo() {
+    let mut rack = Rack::default();
+    let lfo = LfoBuilder::new(LfoShape::Square).hz(0.25).rack(&mut rack);
+    let out: Vec<[f32; 2]> = (0..4)
+        .map(|_| {
+            let o = rack.play(1f32);
+            [o[0], o[1]]
+        })
+        .collect();
+    assert_eq!(out, vec![[1.0, 1.0], [1.0, 1.0], [1.0, 1.0], [-1.0, 0.0]]);
+    lfo.set_shape(&mut rack, LfoShape::Saw);
+    lfo.set_gate(&mut rack, 1.0.into());
+    assert_eq!(rack.mono(1f32), -1.0);
+}
+
+#[test]
+fn lfo_fade_in() {
+    let mut rack = Rack::default();
+    LfoBuilder::new(LfoShape::Square)
+        .hz(0.0)
+        .fade(4.0)
+        .rack(&mut rack);
+    let out: Vec<f32> = (0..6).map(|_| rack.mono(1f32)).collect();
+    assert_eq!(out, vec![0.0, 0.25, 0.5, 0.75, 1.0, 1.0]);
+}
+
+#[test]
+fn lfo_sync() {
+    let mut rack = Rack::default();
+    let clock = TempoClockBuilder::new(60.0).rack(&mut rack);
+    LfoBuilder::new(LfoShape::Saw)
+        .hz(1000.0)
+        .sync(clock.tag())
+        .beats(2.0)
+        .rack(&mut rack);
+    // One cycle every 2 beats, i.e. 8 samples.
+    let out: Vec<f32> = (0..10).map(|_| rack.mono(4f32)).collect();
+    assert_eq!(out[0], out[8]);
+    assert_eq!(out[1], out[9]);
+    assert_ne!(out[0], out[4]);
+}
+
+#[test]
+fn lfo_sample_hold() {
+    let mut rack = Rack::default();
+    LfoBuilder::new(LfoShape::SampleHold)
+        .hz(0.25)
+        .seed(5)
+        .rack(&mut rack);
+    let out: Vec<f32> = (0..8).map(|_| rack.mono(1f32)).collect();
+    assert!(out[0..4].iter().all(|x| *x == out[0]));
+    assert!(out[4..8].iter().all(|x| *x == out[4]));
+    assert_ne!(out[0], out[4]);
+}
+
+#[test]
+fn lfo_random_phase() {
+    fn run(shape: LfoShape, phase: f32) -> Vec<f32> {
+        let mut rack = Rack::default();
+        LfoBuilder::new(shape)
+            .hz(0.25)
+            .phase(phase)
+            .seed(9)
+            .rack(&mut rack);
+        (0..12).map(|_| rack.mono(1f32)).collect()
+    }
+    for shape in [LfoShape::SampleHold, LfoShape::SmoothRandom] {
+        let a = run(shape, 0.0);
+        let b = run(shape, 0.5);
+        assert_ne!(a[0..4], b[0..4]);
+        // After its first half cycle the offset lfo runs two samples ahead.
+        assert_eq!(a[4..12], b[2..10]);
+    }
+}
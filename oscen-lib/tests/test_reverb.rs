@@ -0,0 +1,234 @@
+use oscen::oscillators::*;
+use oscen::rack::*;
+use oscen::reverb::*;
+
+fn impulse(rack: &mut Rack) -> std::sync::Arc<Const> {
+    ConstBuilder::new(1.0.into()).rack(rack)
+}
+
+fn energy(rack: &mut Rack, tag: Tag, samples: usize, sample_rate: f32) -> [f32; 2] {
+    let mut e = [0.0; 2];
+    for _ in 0..samples {
+        rack.play(sample_rate);
+        e[0] += rack.outputs[(tag, 0)].powi(2);
+        e[1] += rack.outputs[(tag, 1)].powi(2);
+    }
+    e
+}
+
+#[test]
+fn freeverb_tail_decays() {
+    let mut rack = Rack::default();
+    let imp = impulse(&mut rack);
+    let fv = FreeverbBuilder::new(imp.tag(), imp.tag()).rack(&mut rack);
+    rack.play(44100.0);
+    imp.set_value(&mut rack, 0.0.into());
+    let early = energy(&mut rack, fv.tag(), 44100, 44100.0);
+    assert!(early[0] > 0.0 && early[1] > 0.0);
+    let late = energy(&mut rack, fv.tag(), 44100, 44100.0);
+    assert!(late[0] < 0.1 * early[0]);
+    assert!(late[1] < 0.1 * early[1]);
+}
+
+#[test]
+fn freeverb_stereo() {
+    let mut rack = Rack::default();
+    let imp = impulse(&mut rack);
+    let fv = FreeverbBuilder::new(imp.tag(), imp.tag()).rack(&mut rack);
+    rack.play(44100.0);
+    imp.set_value(&mut rack, 0.0.into());
+    let mut differ = false;
+    for _ in 0..4410 {
+        rack.play(44100.0);
+        let out = rack.outputs.outputs(fv.tag());
+        differ |= (out[0] - out[1]).abs() > 1e-6;
+    }
+    assert!(differ);
+
+    // With zero width both channels get the same mix.
+    fv.set_width(&mut rack, 0.0.into());
+    for _ in 0..100 {
+        rack.play(44100.0);
+        let out = rack.outputs.outputs(fv.tag());
+        assert!((out[0] - out[1]).abs() < 1e-6);
+    }
+}
+
+#[test]
+fn freeverb_dry() {
+    let mut rack = Rack::default();
+    let imp = impulse(&mut rack);
+    let fv = FreeverbBuilder::new(imp.tag(), imp.tag())
+        .wet(0.0)
+        .dry(0.5)
+        .rack(&mut rack);
+    let out = rack.play(44100.0);
+    assert_eq!(out[0], 0.5);
+    assert_eq!(out[1], 0.5);
+    imp.set_value(&mut rack, 0.0.into());
+    let e = energy(&mut rack, fv.tag(), 4410, 44100.0);
+    assert_eq!(e, [0.0, 0.0]);
+}
+
+#[test]
+fn freeverb_freeze() {
+    let mut rack = Rack::default();
+    let imp = impulse(&mut rack);
+    let fv = FreeverbBuilder::new(imp.tag(), imp.tag()).rack(&mut rack);
+    for _ in 0..100 {
+        rack.play(44100.0);
+    }
+    imp.set_value(&mut rack, 0.0.into());
+    fv.set_frozen(&mut rack, true.into());
+    let early = energy(&mut rack, fv.tag(), 44100, 44100.0);
+    let late = energy(&mut rack, fv.tag(), 44100, 44100.0);
+    assert!(late[0] > 0.9 * early[0]);
+
+    // Frozen reverb ignores new input.
+    imp.set_value(&mut rack, 1.0.into());
+    let held = energy(&mut rack, fv.tag(), 44100, 44100.0);
+    assert!((held[0] - late[0]).abs() < 0.1 * late[0]);
+}
+
+#[test]
+fn freeverb_sample_rate() {
+    let mut rack = Rack::default();
+    let imp = impulse(&mut rack);
+    let fv = FreeverbBuilder::new(imp.tag(), imp.tag()).rack(&mut rack);
+    rack.play(96000.0);
+    imp.set_value(&mut rack, 0.0.into());
+    let e = energy(&mut rack, fv.tag(), 96000, 96000.0);
+    assert!(e[0] > 0.0 && e[0].is_finite());
+}
+
+#[test]
+fn freeverb_above_max_sample_rate() {
+    let mut rack = Rack::default();
+    let imp = impulse(&mut rack);
+    let fv = FreeverbBuilder::new(imp.tag(), imp.tag())
+        .max_sample_rate(44100.0)
+        .rack(&mut rack);
+    rack.play(96000.0);
+    imp.set_value(&mut rack, 0.0.into());
+    let e = energy(&mut rack, fv.tag(), 9600, 96000.0);
+    assert!(e[0] > 0.0 && e[0].is_finite());
+}
+
+fn window_energy(out: &[[f32; 2]], from: usize, to: usize) -> f32 {
+    out[from..to]
+        .iter()
+        .map(|x| x[0] * x[0] + x[1] * x[1])
+        .sum()
+}
+
+fn fdn_impulse<F>(configure: F, samples: usize, sample_rate: f32) -> Vec<[f32; 2]>
+where
+    F: FnOnce(&mut FdnReverbBuilder),
+{
+    let mut rack = Rack::default();
+    let imp = impulse(&mut rack);
+    let mut builder = FdnReverbBuilder::new(imp.tag(), imp.tag());
+    configure(&mut builder);
+    let fdn = builder.rack(&mut rack);
+    let mut out = Vec::with_capacity(samples);
+    for _ in 0..samples {
+        rack.play(sample_rate);
+        imp.set_value(&mut rack, 0.0.into());
+        let o = rack.outputs.outputs(fdn.tag());
+        out.push([o[0], o[1]]);
+    }
+    out
+}
+
+#[test]
+fn fdn_rt60() {
+    let sr = 44100.0;
+    for matrix in [FdnMatrix::Hadamard, FdnMatrix::Householder] {
+        for lines in [8, 16] {
+            let out = fdn_impulse(
+                |b| {
+                    b.lines(lines)
+                        .matrix(matrix)
+                        .decay(0.5)
+                        .damping(0.0)
+                        .dry(0.0)
+                        .wet(1.0);
+                },
+                44100,
+                sr,
+            );
+            // 0.5 seconds apart the energy drops by 60 dB.
+            let early = window_energy(&out, 8820, 13230);
+            let late = window_energy(&out, 30870, 35280);
+            let drop = 10.0 * (early / late).log10();
+            assert!((drop - 60.0).abs() < 6.0, "{matrix:?} {lines}: {drop} dB");
+        }
+    }
+}
+
+#[test]
+fn fdn_damping_shortens_tail() {
+    let sr = 44100.0;
+    let tail = |damping: f32| {
+        let out = fdn_impulse(
+            |b| {
+                b.damping(damping).dry(0.0);
+            },
+            44100,
+            sr,
+        );
+        window_energy(&out, 22050, 44100)
+    };
+    assert!(tail(0.8) < 0.5 * tail(0.0));
+}
+
+#[test]
+fn fdn_pre_delay() {
+    let sr = 44100.0;
+    let out = fdn_impulse(
+        |b| {
+            b.pre_delay(0.1).mod_depth(0.0).dry(0.0);
+        },
+        8000,
+        sr,
+    );
+    // Nothing reaches the output before the pre-delay plus the shortest line.
+    let first = out.iter().position(|x| x[0] != 0.0 || x[1] != 0.0).unwrap();
+    assert_eq!(first, 4410 + 1307);
+}
+
+#[test]
+fn fdn_stereo() {
+    let out = fdn_impulse(
+        |b| {
+            b.dry(0.0);
+        },
+        44100,
+        44100.0,
+    );
+    assert!(out.iter().any(|x| (x[0] - x[1]).abs() > 1e-6));
+    assert!(out.iter().all(|x| x[0].is_finite() && x[1].is_finite()));
+}
+
+#[test]
+#[should_panic]
+fn fdn_lines() {
+    let mut rack = Rack::default();
+    let imp = impulse(&mut rack);
+    FdnReverbBuilder::new(imp.tag(), imp.tag())
+        .lines(12)
+        .rack(&mut rack);
+}
+
+#[test]
+fn fdn_above_max_sample_rate() {
+    let out = fdn_impulse(
+        |b| {
+            b.max_sample_rate(44100.0);
+        },
+        9600,
+        96000.0,
+    );
+    let e = window_energy(&out, 0, out.len());
+    assert!(e > 0.0 && e.is_finite());
+}
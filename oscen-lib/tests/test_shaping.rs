@@ -0,0 +1,145 @@
+use oscen::oscillators::*;
+use oscen::rack::*;
+use oscen::shaping::*;
+
+fn shape(curve: DistortionCurve, drive: f32, bias: f32, xs: &[f32]) -> Vec<f32> {
+    let mut rack = Rack::default();
+    let c = ConstBuilder::new(0.0.into()).rack(&mut rack);
+    let d = DistortionBuilder::new(c.tag(), curve)
+        .drive(drive)
+        .bias(bias)
+        .rack(&mut rack);
+    xs.iter()
+        .map(|x| {
+            c.set_value(&mut rack, (*x).into());
+            rack.play(44100.0);
+            rack.outputs[(d.tag(), 0)]
+        })
+        .collect()
+}
+
+#[test]
+fn distortion_curves() {
+    let xs = [-3.0, -0.5, 0.0, 0.5, 3.0];
+    assert_eq!(
+        shape(DistortionCurve::Hard, 1.0, 0.0, &xs),
+        vec![-1.0, -0.5, 0.0, 0.5, 1.0]
+    );
+    let soft = shape(DistortionCurve::Soft, 1.0, 0.0, &xs);
+    for (y, x) in soft.iter().zip(xs.iter()) {
+        assert_eq!(*y, x.tanh());
+    }
+    assert_eq!(
+        shape(DistortionCurve::Foldback, 1.0, 0.0, &[-1.5, 0.25, 1.5, 3.5]),
+        vec![-0.5, 0.25, 0.5, -0.5]
+    );
+    // The tube curve clips the negative half lower than the positive.
+    let tube = shape(DistortionCurve::Tube, 10.0, 0.0, &[-1.0, 1.0]);
+    assert!(
+        tube[1] > 0.99 && tube[0] > -0.81 && tube[0] < -0.79,
+        "{tube:?}"
+    );
+}
+
+#[test]
+fn distortion_drive_and_bias() {
+    let y = shape(DistortionCurve::Hard, 4.0, 0.0, &[0.2, 0.5]);
+    assert!((y[0] - 0.8).abs() < 1e-6);
+    assert_eq!(y[1], 1.0);
+    // Bias makes the curve asymmetric but keeps silence at zero.
+    let y = shape(DistortionCurve::Hard, 1.0, 0.5, &[0.0, 0.8, -0.8]);
+    assert_eq!(y[0], 0.0);
+    assert!((y[1] - 0.5).abs() < 1e-6);
+    assert!((y[2] + 0.8).abs() < 1e-6);
+}
+
+#[test]
+fn distortion_set_curve() {
+    let mut rack = Rack::default();
+    let c = ConstBuilder::new(2.0.into()).rack(&mut rack);
+    let d = DistortionBuilder::new(c.tag(), DistortionCurve::Hard).rack(&mut rack);
+    assert_eq!(rack.mono(44100.0), 1.0);
+    d.set_curve(&mut rack, DistortionCurve::Soft);
+    assert_eq!(d.curve(&rack), DistortionCurve::Soft);
+    assert_eq!(rack.mono(44100.0), 2f32.tanh());
+}
+
+#[test]
+fn bit_crusher_quantizes() {
+    let mut rack = Rack::default();
+    let c = ConstBuilder::new(0.3.into()).rack(&mut rack);
+    let bc = BitCrusherBuilder::new(c.tag()).bits(3.0).rack(&mut rack);
+    // 3 bits give steps of 0.25.
+    assert_eq!(rack.mono(44100.0), 0.25);
+    c.set_value(&mut rack, (-0.9).into());
+    assert_eq!(rack.mono(44100.0), -1.0);
+    bc.set_bits(&mut rack, 16.0.into());
+    c.set_value(&mut rack, 0.3.into());
+    assert!((rack.mono(44100.0) - 0.3).abs() < 1.0 / 32768.0);
+}
+
+#[test]
+fn bit_crusher_sample_and_hold() {
+    let sr = 44100.0;
+    let mut rack = Rack::default();
+    let saw = OscBuilder::new(saw_osc).hz(100.0).rack(&mut rack);
+    BitCrusherBuilder::new(saw.tag())
+        .bits(24.0)
+        .rate(sr / 4.0)
+        .rack(&mut rack);
+    let out: Vec<f32> = (0..16).map(|_| rack.mono(sr)).collect();
+    for frame in out.chunks(4) {
+        assert!(frame.iter().all(|x| *x == frame[0]), "{out:?}");
+    }
+    assert!(out[0] != out[4]);
+}
+
+#[test]
+fn bit_crusher_dither() {
+    let mut rack = Rack::default();
+    let c = ConstBuilder::new(0.1.into()).rack(&mut rack);
+    BitCrusherBuilder::new(c.tag())
+        .bits(2.0)
+        .dither(true)
+        .seed(7)
+        .rack(&mut rack);
+    // Without dither 0.1 always rounds to 0, dithered it averages out to 0.1.
+    let n = 100_000;
+    let mean = (0..n).map(|_| rack.mono(44100.0)).sum::<f32>() / n as f32;
+    assert!((mean - 0.1).abs() < 0.01, "{mean}");
+}
+
+#[test]
+fn waveshaper_table() {
+    let mut rack = Rack::default();
+    let c = ConstBuilder::new(0.0.into()).rack(&mut rack);
+    let ws = WaveshaperBuilder::new(c.tag(), vec![-1.0, 0.0, 0.0, 1.0]).rack(&mut rack);
+    let at = |x: f32, rack: &mut Rack| {
+        c.set_value(rack, x.into());
+        rack.mono(44100.0)
+    };
+    assert_eq!(at(-1.0, &mut rack), -1.0);
+    assert_eq!(at(0.0, &mut rack), 0.0);
+    assert!((at(2.0 / 3.0, &mut rack) - 0.5).abs() < 1e-6);
+    assert_eq!(at(2.0, &mut rack), 1.0);
+
+    ws.set_table(&mut rack, &[1.0, -1.0]);
+    assert_eq!(ws.table(&rack), vec![1.0, -1.0]);
+    assert!((at(0.5, &mut rack) + 0.5).abs() < 1e-6);
+}
+
+#[test]
+fn waveshaper_from_fn() {
+    let mut rack = Rack::default();
+    let c = ConstBuilder::new(0.5.into()).rack(&mut rack);
+    WaveshaperBuilder::from_fn(c.tag(), |x| x * x * x, 1025)
+        .drive(0.5)
+        .rack(&mut rack);
+    assert!((rack.mono(44100.0) - 0.015625).abs() < 1e-4);
+}
+
+#[test]
+#[should_panic(expected = "at least 2 points")]
+fn waveshaper_from_fn_single_point() {
+    WaveshaperBuilder::from_fn(0.into(), |x| x, 1);
+}